@@ -0,0 +1,414 @@
+// checkerboard inner corner detection
+use std::collections::{HashMap, VecDeque};
+use super::super::processing::{linalg, luminance, Image};
+use super::Pattern;
+
+// the frame is reduced to this long edge before searching for corners
+const DETECT_SIZE: u32 = 1024;
+// corners weaker than this fraction of the strongest one are ignored
+const RESPONSE_THRESHOLD: f32 = 0.05;
+// a predicted grid position snaps to a candidate within this fraction of the grid step
+const SNAP_TOLERANCE: f64 = 0.35;
+// number of strongest candidates tried as the seed of the grid
+const SEED_TRIES: usize = 16;
+
+// inner corners in full resolution pixels (pixel centers on integers),
+// row by row as Pattern::object_points. None when the board is not found.
+pub fn find_corners(img: &Image, pattern: &Pattern)->Option<Vec<(f64, f64)>>{
+    let scale = img.width().max(img.height()).div_ceil(DETECT_SIZE).max(1);
+    let (width, height, lum) = reduce(img, scale);
+    let lum = blur(&blur(&lum, width, height), width, height);
+    let candidates = saddle_points(&lum, width, height, pattern.cols * pattern.rows * 4);
+    let grid = (0..SEED_TRIES.min(candidates.len())).find_map(|seed|grow_grid(&candidates, seed, pattern))?;
+    let offset = (scale as f64 - 1.0) * 0.5;
+    let full_lum = |x: i64, y: i64|->f32{
+        let x = x.max(0).min(img.width() as i64 - 1) as u32;
+        let y = y.max(0).min(img.height() as i64 - 1) as u32;
+        luminance(img.get(x, y))
+    };
+    let window = (scale as i64 * 2).max(5);
+    Some(grid.iter().map(|&(x, y)|{
+        refine(&full_lum, (x * scale as f64 + offset, y * scale as f64 + offset), window)
+    }).collect())
+}
+
+fn reduce(img: &Image, scale: u32)->(usize, usize, Vec<f32>){
+    let (width, height) = ((img.width() / scale).max(1), (img.height() / scale).max(1));
+    let mut lum = vec![0.0; (width * height) as usize];
+    for y in 0..height{
+        for x in 0..width{
+            let mut sum = 0.0;
+            for sy in (y * scale)..((y + 1) * scale){
+                for sx in (x * scale)..((x + 1) * scale){
+                    sum += luminance(img.get(sx, sy));
+                }
+            }
+            lum[(y * width + x) as usize] = sum / (scale * scale) as f32;
+        }
+    }
+    (width as usize, height as usize, lum)
+}
+
+// 3x3 box filter
+fn blur(lum: &[f32], width: usize, height: usize)->Vec<f32>{
+    let mut out = lum.to_vec();
+    for y in 1..height.saturating_sub(1){
+        for x in 1..width.saturating_sub(1){
+            let mut sum = 0.0;
+            for dy in 0..3{
+                for dx in 0..3{
+                    sum += lum[(y + dy - 1) * width + x + dx - 1];
+                }
+            }
+            out[y * width + x] = sum / 9.0;
+        }
+    }
+    out
+}
+
+// local maxima of the negative hessian determinant, strongest first
+fn saddle_points(lum: &[f32], width: usize, height: usize, limit: usize)->Vec<(f64, f64)>{
+    const STEP: usize = 2;
+    const NMS_RADIUS: usize = 3;
+    let at = |x: usize, y: usize|lum[y * width + x];
+    let mut response = vec![0.0f32; lum.len()];
+    let mut max_response = 0.0f32;
+    for y in STEP..height.saturating_sub(STEP){
+        for x in STEP..width.saturating_sub(STEP){
+            let c = at(x, y);
+            let ixx = at(x + STEP, y) - 2.0 * c + at(x - STEP, y);
+            let iyy = at(x, y + STEP) - 2.0 * c + at(x, y - STEP);
+            let ixy = (at(x + STEP, y + STEP) - at(x + STEP, y - STEP) - at(x - STEP, y + STEP) + at(x - STEP, y - STEP)) * 0.25;
+            let r = (ixy * ixy - ixx * iyy).max(0.0);
+            response[y * width + x] = r;
+            max_response = max_response.max(r);
+        }
+    }
+    let threshold = max_response * RESPONSE_THRESHOLD;
+    let mut points = Vec::new();
+    for y in NMS_RADIUS..height.saturating_sub(NMS_RADIUS){
+        for x in NMS_RADIUS..width.saturating_sub(NMS_RADIUS){
+            let r = response[y * width + x];
+            if r <= threshold || r <= 0.0{
+                continue;
+            }
+            let mut is_max = true;
+            'window: for ny in (y - NMS_RADIUS)..=(y + NMS_RADIUS){
+                for nx in (x - NMS_RADIUS)..=(x + NMS_RADIUS){
+                    let other = response[ny * width + nx];
+                    // ties go to the first pixel in scan order
+                    if other > r || (other == r && (ny, nx) < (y, x)){
+                        is_max = false;
+                        break 'window;
+                    }
+                }
+            }
+            if is_max && is_checker_corner(&at, width, height, x, y){
+                points.push((r, x as f64, y as f64));
+            }
+        }
+    }
+    points.sort_by(|a, b|b.0.total_cmp(&a.0));
+    points.truncate(limit);
+    points.into_iter().map(|(_, x, y)|(x, y)).collect()
+}
+
+// a checker corner has two dark and two bright sectors around it, opposite sectors alike.
+// edges and blobs have fewer.
+fn is_checker_corner<F: Fn(usize, usize)->f32>(at: &F, width: usize, height: usize, x: usize, y: usize)->bool{
+    const SAMPLES: usize = 24;
+    const RADIUS: f64 = 4.0;
+    if x < RADIUS as usize + 1 || y < RADIUS as usize + 1 || x + RADIUS as usize + 1 >= width || y + RADIUS as usize + 1 >= height{
+        return false;
+    }
+    let ring: Vec<f32> = (0..SAMPLES).map(|k|{
+        let angle = k as f64 * std::f64::consts::PI * 2.0 / SAMPLES as f64;
+        let sx = (x as f64 + angle.cos() * RADIUS).round() as usize;
+        let sy = (y as f64 + angle.sin() * RADIUS).round() as usize;
+        at(sx, sy)
+    }).collect();
+    let max = ring.iter().cloned().fold(f32::MIN, f32::max);
+    let min = ring.iter().cloned().fold(f32::MAX, f32::min);
+    if max - min < 0.05{
+        return false;
+    }
+    let mid = (max + min) * 0.5;
+    let bright: Vec<bool> = ring.iter().map(|v|*v > mid).collect();
+    let changes = (0..SAMPLES).filter(|&k|bright[k] != bright[(k + 1) % SAMPLES]).count();
+    let symmetric = (0..SAMPLES / 2).filter(|&k|bright[k] == bright[k + SAMPLES / 2]).count();
+    changes == 4 && symmetric * 10 >= SAMPLES / 2 * 8
+}
+
+// walk from the seed to the neighbouring corners and index them on a grid
+fn grow_grid(points: &[(f64, f64)], seed: usize, pattern: &Pattern)->Option<Vec<(f64, f64)>>{
+    let sub = |a: (f64, f64), b: (f64, f64)|(a.0 - b.0, a.1 - b.1);
+    let len = |v: (f64, f64)|(v.0 * v.0 + v.1 * v.1).sqrt();
+    let origin = points[seed];
+    // the two grid steps from the closest neighbours that are not parallel
+    let mut neighbours: Vec<usize> = (0..points.len()).filter(|&i|i != seed).collect();
+    neighbours.sort_by(|&a, &b|len(sub(points[a], origin)).total_cmp(&len(sub(points[b], origin))));
+    let step_i = sub(points[*neighbours.first()?], origin);
+    let step_j = neighbours.iter().skip(1).map(|&n|sub(points[n], origin)).find(|v|{
+        let cos = (v.0 * step_i.0 + v.1 * step_i.1) / (len(*v) * len(step_i));
+        cos.abs() < 0.5
+    })?;
+
+    let mut grid: HashMap<(i64, i64), usize> = HashMap::new();
+    let mut used = vec![false; points.len()];
+    grid.insert((0, 0), seed);
+    used[seed] = true;
+    let mut queue = VecDeque::new();
+    queue.push_back((0i64, 0i64));
+    while let Some((i, j)) = queue.pop_front(){
+        let p = points[grid[&(i, j)]];
+        for &(di, dj) in &[(1i64, 0i64), (-1, 0), (0, 1), (0, -1)]{
+            let next = (i + di, j + dj);
+            if grid.contains_key(&next){
+                continue;
+            }
+            // prefer the local step, perspective changes it across the board
+            let step = match grid.get(&(i - di, j - dj)){
+                Some(&back)=>sub(p, points[back]),
+                None=>{
+                    let s = if di != 0 { step_i } else { step_j };
+                    let sign = (di + dj) as f64;
+                    (s.0 * sign, s.1 * sign)
+                }
+            };
+            let predicted = (p.0 + step.0, p.1 + step.1);
+            let tolerance = len(step) * SNAP_TOLERANCE;
+            let found = (0..points.len())
+                .filter(|&k|!used[k])
+                .map(|k|(k, len(sub(points[k], predicted))))
+                .filter(|&(_, d)|d < tolerance)
+                .min_by(|a, b|a.1.total_cmp(&b.1));
+            if let Some((k, _)) = found{
+                used[k] = true;
+                grid.insert(next, k);
+                queue.push_back(next);
+            }
+        }
+        if grid.len() > pattern.cols * pattern.rows{
+            return None;
+        }
+    }
+    if grid.len() != pattern.cols * pattern.rows{
+        return None;
+    }
+
+    let min_i = grid.keys().map(|k|k.0).min()?;
+    let max_i = grid.keys().map(|k|k.0).max()?;
+    let min_j = grid.keys().map(|k|k.1).min()?;
+    let max_j = grid.keys().map(|k|k.1).max()?;
+    let (extent_i, extent_j) = ((max_i - min_i + 1) as usize, (max_j - min_j + 1) as usize);
+    let transpose = if (extent_i, extent_j) == (pattern.cols, pattern.rows){
+        false
+    }
+    else if (extent_i, extent_j) == (pattern.rows, pattern.cols){
+        true
+    }
+    else{
+        return None;
+    };
+    let mut ordered: HashMap<(usize, usize), (f64, f64)> = HashMap::new();
+    for (&(i, j), &k) in &grid{
+        let (i, j) = ((i - min_i) as usize, (j - min_j) as usize);
+        let key = if transpose { (j, i) } else { (i, j) };
+        ordered.insert(key, points[k]);
+    }
+    // columns run to the right of rows in the image (y down) so the board is not mirrored
+    let a = sub(ordered[&(1, 0)], ordered[&(0, 0)]);
+    let b = sub(ordered[&(0, 1)], ordered[&(0, 0)]);
+    let mirrored = a.0 * b.1 - a.1 * b.0 < 0.0;
+    let mut corners = Vec::with_capacity(pattern.cols * pattern.rows);
+    for row in 0..pattern.rows{
+        for col in 0..pattern.cols{
+            let row = if mirrored { pattern.rows - 1 - row } else { row };
+            corners.push(ordered[&(col, row)]);
+        }
+    }
+    Some(corners)
+}
+
+// move the corner to where the image gradients in the window are orthogonal to it
+fn refine<F: Fn(i64, i64)->f32>(lum: &F, start: (f64, f64), window: i64)->(f64, f64){
+    let mut corner = start;
+    for _ in 0..20{
+        let (cx, cy) = (corner.0.round() as i64, corner.1.round() as i64);
+        let mut a = [[0.0; 3]; 3];
+        let mut b = [0.0; 3];
+        for y in (cy - window)..=(cy + window){
+            for x in (cx - window)..=(cx + window){
+                let gx = ((lum(x + 1, y) - lum(x - 1, y)) * 0.5) as f64;
+                let gy = ((lum(x, y + 1) - lum(x, y - 1)) * 0.5) as f64;
+                a[0][0] += gx * gx;
+                a[0][1] += gx * gy;
+                a[1][1] += gy * gy;
+                b[0] += gx * gx * x as f64 + gx * gy * y as f64;
+                b[1] += gx * gy * x as f64 + gy * gy * y as f64;
+            }
+        }
+        a[1][0] = a[0][1];
+        a[2][2] = 1.0;
+        let next = match linalg::invert3(&a){
+            Some(inv)=>{
+                let p = linalg::mul3v(&inv, &b);
+                (p[0], p[1])
+            }
+            None=>return corner,
+        };
+        let moved = ((next.0 - corner.0).powi(2) + (next.1 - corner.1).powi(2)).sqrt();
+        if ((next.0 - start.0).powi(2) + (next.1 - start.1).powi(2)).sqrt() > window as f64{
+            // ran off, the coarse position is better
+            return start;
+        }
+        corner = next;
+        if moved < 0.01{
+            break;
+        }
+    }
+    corner
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const PATTERN: Pattern = Pattern{cols: 7, rows: 5, square_size: 1.0};
+    const SUB_PIXEL: f64 = 0.3;
+
+    // board coordinates in squares, the inner corner (col, row) is at (col + 1, row + 1)
+    fn board_to_image(quad: [(f64, f64); 4])->linalg::Mat3{
+        let (w, h) = ((PATTERN.cols + 1) as f64, (PATTERN.rows + 1) as f64);
+        linalg::homography(&[(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)], &quad).unwrap()
+    }
+
+    // the board seen through h on a white background, 4x4 samples per pixel for smooth edges.
+    // the 16 levels of an edge pixel limit how well a corner can be located to about a fifth of a pixel.
+    fn render(h: &linalg::Mat3, width: u32, height: u32)->Image{
+        const SAMPLES: u32 = 4;
+        let inverse = linalg::invert3(h).unwrap();
+        let (w, h) = ((PATTERN.cols + 1) as f64, (PATTERN.rows + 1) as f64);
+        let mut img = Image::new(width, height);
+        for y in 0..height{
+            for x in 0..width{
+                let mut sum = 0.0;
+                for sy in 0..SAMPLES{
+                    for sx in 0..SAMPLES{
+                        let u = x as f64 + (sx as f64 + 0.5) / SAMPLES as f64 - 0.5;
+                        let v = y as f64 + (sy as f64 + 0.5) / SAMPLES as f64 - 0.5;
+                        let (bx, by) = linalg::project(&inverse, u, v);
+                        let dark = bx >= 0.0 && by >= 0.0 && bx < w && by < h && (bx.floor() + by.floor()) as i64 % 2 == 0;
+                        sum += if dark { 0.1 } else { 0.9 };
+                    }
+                }
+                let value = sum / (SAMPLES * SAMPLES) as f32;
+                img.set(x, y, [value; 3]);
+            }
+        }
+        img
+    }
+
+    fn truth(h: &linalg::Mat3)->Vec<(f64, f64)>{
+        PATTERN.object_points().iter().map(|&(col, row)|linalg::project(h, col + 1.0, row + 1.0)).collect()
+    }
+
+    fn distance(a: (f64, f64), b: (f64, f64))->f64{
+        ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+    }
+
+    // the largest error of found against the true corners relabeled by order, None when they are too far apart to be the same order
+    fn error_in_order<F: Fn(usize, usize)->(usize, usize)>(found: &[(f64, f64)], truth: &[(f64, f64)], order: F)->Option<f64>{
+        let mut error = 0.0f64;
+        for row in 0..PATTERN.rows{
+            for col in 0..PATTERN.cols{
+                let (c, r) = order(col, row);
+                error = error.max(distance(found[row * PATTERN.cols + col], truth[r * PATTERN.cols + c]));
+            }
+        }
+        if error < 2.0 { Some(error) } else { None }
+    }
+
+    // a board can be read from either end, so the order is fixed up to a half turn.
+    // a mirrored board comes out with its rows the other way, so the columns still run to the right of the rows.
+    fn check(found: &[(f64, f64)], truth: &[(f64, f64)], mirrored: bool)->f64{
+        let (cols, rows) = (PATTERN.cols, PATTERN.rows);
+        assert_eq!(found.len(), cols * rows);
+        let error = if mirrored{
+            error_in_order(found, truth, |c, r|(c, rows - 1 - r)).or_else(||error_in_order(found, truth, |c, r|(cols - 1 - c, r)))
+        }
+        else{
+            error_in_order(found, truth, |c, r|(c, r)).or_else(||error_in_order(found, truth, |c, r|(cols - 1 - c, rows - 1 - r)))
+        };
+        let a = (found[1].0 - found[0].0, found[1].1 - found[0].1);
+        let b = (found[cols].0 - found[0].0, found[cols].1 - found[0].1);
+        assert!(a.0 * b.1 - a.1 * b.0 > 0.0, "the corners are mirrored");
+        error.unwrap_or_else(||panic!("the corners are out of order: {:?}", found))
+    }
+
+    #[test]
+    fn find_corners_on_a_board_in_perspective(){
+        let h = board_to_image([(100.0, 80.0), (540.0, 100.0), (520.0, 400.0), (120.0, 380.0)]);
+        let found = find_corners(&render(&h, 640, 480), &PATTERN).unwrap();
+        let error = check(&found, &truth(&h), false);
+        assert!(error < SUB_PIXEL, "sub-pixel error {}", error);
+    }
+
+    #[test]
+    fn find_corners_on_a_board_turned_a_quarter(){
+        // the columns of the board run down the image
+        let h = board_to_image([(460.0, 60.0), (450.0, 420.0), (180.0, 410.0), (190.0, 70.0)]);
+        let found = find_corners(&render(&h, 640, 480), &PATTERN).unwrap();
+        let error = check(&found, &truth(&h), false);
+        assert!(error < SUB_PIXEL, "sub-pixel error {}", error);
+    }
+
+    #[test]
+    fn find_corners_on_a_mirrored_board(){
+        let h = board_to_image([(540.0, 80.0), (100.0, 100.0), (120.0, 400.0), (520.0, 380.0)]);
+        let found = find_corners(&render(&h, 640, 480), &PATTERN).unwrap();
+        let error = check(&found, &truth(&h), true);
+        assert!(error < SUB_PIXEL, "sub-pixel error {}", error);
+    }
+
+    #[test]
+    fn find_corners_without_a_board(){
+        let mut img = Image::new(320, 240);
+        img.map(|_|[0.5; 3]);
+        assert!(find_corners(&img, &PATTERN).is_none());
+        // a board with other dimensions is not taken for the pattern
+        let h = board_to_image([(100.0, 80.0), (540.0, 100.0), (520.0, 400.0), (120.0, 380.0)]);
+        assert!(find_corners(&render(&h, 640, 480), &Pattern{cols: 6, rows: 5, square_size: 1.0}).is_none());
+    }
+
+    #[test]
+    fn grow_grid_orders_shuffled_corners_and_skips_clutter(){
+        let h = board_to_image([(100.0, 80.0), (540.0, 100.0), (520.0, 400.0), (120.0, 380.0)]);
+        let truth = truth(&h);
+        // every third corner first, then the rest, with points off the grid in between
+        let mut points: Vec<(f64, f64)> = truth.iter().step_by(3).cloned().collect();
+        points.push((20.0, 20.0));
+        points.extend(truth.iter().enumerate().filter(|(i, _)|i % 3 != 0).map(|(_, p)|*p));
+        points.push((620.0, 460.0));
+        let found = grow_grid(&points, 5, &PATTERN).unwrap();
+        assert_eq!(check(&found, &truth, false), 0.0);
+        // a seed off the grid finds no neighbours that make a grid
+        assert!(grow_grid(&points, truth.len() / 3 + 1, &PATTERN).is_none());
+        // nor does a board with corners missing
+        assert!(grow_grid(&truth[1..], 3, &PATTERN).is_none());
+    }
+
+    #[test]
+    fn refine_moves_to_the_corner(){
+        let h = board_to_image([(100.0, 80.0), (540.0, 100.0), (520.0, 400.0), (120.0, 380.0)]);
+        let img = render(&h, 640, 480);
+        let lum = |x: i64, y: i64|luminance(img.get(x.clamp(0, 639) as u32, y.clamp(0, 479) as u32));
+        for &corner in truth(&h).iter().step_by(4){
+            let refined = refine(&lum, (corner.0.round() + 1.0, corner.1.round() - 2.0), 5);
+            assert!(distance(refined, corner) < SUB_PIXEL, "{:?} != {:?}", refined, corner);
+        }
+        // a flat window has no corner to move to
+        let flat = |_: i64, _: i64|0.5;
+        assert_eq!(refine(&flat, (10.0, 10.0), 5), (10.0, 10.0));
+    }
+}
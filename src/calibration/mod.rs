@@ -0,0 +1,141 @@
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use super::config;
+use super::processing::Image;
+use super::error::{Error, Result};
+
+mod corners;
+mod solver;
+
+// checkerboard shots live in <root>/caribration/wide and <root>/caribration/tele
+pub const CARIBRATION_DIR_NAME: &str = "caribration";
+pub const WIDE_DIR_NAME: &str = "wide";
+pub const TELE_DIR_NAME: &str = "tele";
+
+// checkerboard given by its inner corners
+pub struct Pattern{
+    pub cols: usize,
+    pub rows: usize,
+    // edge length of a square, only scales the board poses
+    pub square_size: f64,
+}
+
+impl Default for Pattern{
+    fn default()->Self{
+        Pattern{cols: 9, rows: 6, square_size: 1.0}
+    }
+}
+
+impl Pattern{
+    // board coordinates of the inner corners, row by row
+    pub fn object_points(&self)->Vec<(f64, f64)>{
+        let mut points = Vec::with_capacity(self.cols * self.rows);
+        for row in 0..self.rows{
+            for col in 0..self.cols{
+                points.push((col as f64 * self.square_size, row as f64 * self.square_size));
+            }
+        }
+        points
+    }
+}
+
+// outcome of one end of the zoom
+#[derive(Serialize, Debug)]
+pub struct SetReport{
+    pub focal_length: f32,
+    pub images: usize,
+    // size of the images in pixels
+    pub width: u32,
+    pub height: u32,
+    // files where the board was not found
+    pub rejected: Vec<String>,
+    // rms reprojection error in pixels
+    pub reprojection_error: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Report{
+    pub wide: SetReport,
+    pub tele: Option<SetReport>,
+}
+
+fn list_images(dir: &Path)->Vec<PathBuf>{
+    let mut images = Vec::new();
+    if let Ok(entries) = dir.read_dir(){
+        for entry in entries.flatten(){
+            let path = entry.path();
+            let is_image = path.extension()
+                .and_then(|ext|ext.to_str())
+                .map(|ext|matches!(ext.to_ascii_lowercase().as_str(), "jpg" | "jpeg" | "png"))
+                .unwrap_or(false);
+            if is_image{
+                images.push(path);
+            }
+        }
+    }
+    images.sort();
+    images
+}
+
+// calibrate from every checkerboard image in dir
+pub fn calibrate_images(dir: &Path, pattern: &Pattern, focal_length: f32)->Result<(solver::Intrinsics, SetReport)>{
+    let images = list_images(dir);
+    if images.is_empty(){
+        return Err(Error::processing("calibrate the lens", &format!("no checkerboard images in {}", dir.display())));
+    }
+    let mut views = Vec::new();
+    let mut rejected = Vec::new();
+    let mut size = None;
+    for path in &images{
+        let img = Image::load_linear(path)?;
+        if *size.get_or_insert((img.width(), img.height())) != (img.width(), img.height()){
+            return Err(Error::processing("calibrate the lens", &format!("{} differs in size from the other images", path.display())));
+        }
+        match corners::find_corners(&img, pattern){
+            Some(found)=>views.push(found),
+            None=>rejected.push(path.file_name().map(|n|n.to_string_lossy().into_owned()).unwrap_or_default()),
+        }
+    }
+    let intrinsics = solver::calibrate(&views, &pattern.object_points())?;
+    let (width, height) = size.unwrap_or_default();
+    let report = SetReport{
+        focal_length,
+        images: images.len(),
+        width,
+        height,
+        rejected,
+        reprojection_error: intrinsics.error,
+    };
+    Ok((intrinsics, report))
+}
+
+// calibrate both ends of the lens and store them in the project root.
+// the tele set is optional, a prime lens only has the wide one.
+pub fn calibrate_project(root: &Path, pattern: &Pattern, focal_length_wide: f32, focal_length_tele: f32)->Result<Report>{
+    let dir = root.join(CARIBRATION_DIR_NAME);
+    let mut info = config::LensCaribrationInfo::new();
+    let (wide, wide_report) = calibrate_images(&dir.join(WIDE_DIR_NAME), pattern, focal_length_wide)?;
+    info.set_wide(focal_length_wide, to_f32(&wide.matrix), to_f32(&wide.dist));
+    info.set_image_size(wide_report.width, wide_report.height);
+    let tele_dir = dir.join(TELE_DIR_NAME);
+    let tele_report = if tele_dir.exists(){
+        let (tele, tele_report) = calibrate_images(&tele_dir, pattern, focal_length_tele)?;
+        // both ends are interpolated, so their matrices have to be in the same pixels
+        if (tele_report.width, tele_report.height) != (wide_report.width, wide_report.height){
+            let message = format!("the tele images are {}x{}, the wide ones {}x{}", tele_report.width, tele_report.height, wide_report.width, wide_report.height);
+            return Err(Error::processing("calibrate the lens", &message));
+        }
+        info.set_tele(focal_length_tele, to_f32(&tele.matrix), to_f32(&tele.dist));
+        Some(tele_report)
+    }
+    else{
+        None
+    };
+    let path = root.join(config::LENS_CARIBRATION_FILE_NAME);
+    info.save(&path)?;
+    Ok(Report{wide: wide_report, tele: tele_report})
+}
+
+fn to_f32(values: &[f64])->Vec<f32>{
+    values.iter().map(|v|*v as f32).collect()
+}
@@ -0,0 +1,328 @@
+// intrinsics and distortion from planar views (zhang's method refined by levenberg-marquardt)
+use super::super::processing::linalg::{self, Mat3};
+use super::super::error::{Error, Result};
+
+const DISTORTION_COUNT: usize = 5;
+const INTRINSIC_COUNT: usize = 4 + DISTORTION_COUNT;
+const VIEW_PARAM_COUNT: usize = 6;
+const MAX_ITERATIONS: usize = 50;
+
+pub struct Intrinsics{
+    // fx, 0, cx, 0, fy, cy, 0, 0, 1
+    pub matrix: [f64; 9],
+    // k1, k2, p1, p2, k3
+    pub dist: [f64; DISTORTION_COUNT],
+    // rms reprojection error in pixels
+    pub error: f64,
+}
+
+// views hold the image positions of the board points given in object (on z = 0)
+pub fn calibrate(views: &[Vec<(f64, f64)>], object: &[(f64, f64)])->Result<Intrinsics>{
+    if views.len() < 3{
+        return Err(Error::processing("calibrate the lens", &format!("at least 3 views of the board are needed, {} found", views.len())));
+    }
+    let homographies: Vec<Mat3> = views.iter()
+        .map(|view|homography(object, view))
+        .collect::<Option<_>>()
+        .ok_or_else(||Error::processing("calibrate the lens", "the homography of a board view could not be estimated"))?;
+    let k = initial_intrinsics(&homographies)?;
+    let mut params = vec![k[0][0], k[1][1], k[0][2], k[1][2], 0.0, 0.0, 0.0, 0.0, 0.0];
+    for h in &homographies{
+        params.extend_from_slice(&initial_extrinsics(&k, h));
+    }
+    let error = refine(&mut params, views, object);
+    Ok(Intrinsics{
+        matrix: [params[0], 0.0, params[2], 0.0, params[1], params[3], 0.0, 0.0, 1.0],
+        dist: [params[4], params[5], params[6], params[7], params[8]],
+        error,
+    })
+}
+
+// normalised dlt
+fn homography(src: &[(f64, f64)], dst: &[(f64, f64)])->Option<Mat3>{
+    if src.len() != dst.len() || src.len() < 4{
+        return None;
+    }
+    let ts = normalization(src);
+    let td = normalization(dst);
+    let mut ata = vec![vec![0.0; 9]; 9];
+    for (s, d) in src.iter().zip(dst.iter()){
+        let (x, y) = linalg::project(&ts, s.0, s.1);
+        let (u, v) = linalg::project(&td, d.0, d.1);
+        let rows = [
+            [x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y, -u],
+            [0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y, -v],
+        ];
+        for row in &rows{
+            for i in 0..9{
+                for j in 0..9{
+                    ata[i][j] += row[i] * row[j];
+                }
+            }
+        }
+    }
+    let h = linalg::null_vector(ata);
+    let hn = [[h[0], h[1], h[2]], [h[3], h[4], h[5]], [h[6], h[7], h[8]]];
+    let h = linalg::mul3(&linalg::mul3(&linalg::invert3(&td)?, &hn), &ts);
+    if h[2][2].abs() < 1e-12{
+        return None;
+    }
+    let mut r = h;
+    for row in r.iter_mut(){
+        for v in row.iter_mut(){
+            *v /= h[2][2];
+        }
+    }
+    Some(r)
+}
+
+// moves the centroid to the origin and the mean distance to sqrt(2)
+fn normalization(points: &[(f64, f64)])->Mat3{
+    let n = points.len() as f64;
+    let cx = points.iter().map(|p|p.0).sum::<f64>() / n;
+    let cy = points.iter().map(|p|p.1).sum::<f64>() / n;
+    let mean = points.iter().map(|p|((p.0 - cx).powi(2) + (p.1 - cy).powi(2)).sqrt()).sum::<f64>() / n;
+    let s = if mean > 1e-12 { std::f64::consts::SQRT_2 / mean } else { 1.0 };
+    [[s, 0.0, -s * cx], [0.0, s, -s * cy], [0.0, 0.0, 1.0]]
+}
+
+// closed form solution of the image of the absolute conic
+fn initial_intrinsics(homographies: &[Mat3])->Result<Mat3>{
+    let v = |h: &Mat3, i: usize, j: usize|->[f64; 6]{
+        [
+            h[0][i] * h[0][j],
+            h[0][i] * h[1][j] + h[1][i] * h[0][j],
+            h[1][i] * h[1][j],
+            h[2][i] * h[0][j] + h[0][i] * h[2][j],
+            h[2][i] * h[1][j] + h[1][i] * h[2][j],
+            h[2][i] * h[2][j],
+        ]
+    };
+    let mut vtv = vec![vec![0.0; 6]; 6];
+    for h in homographies{
+        let v12 = v(h, 0, 1);
+        let v11 = v(h, 0, 0);
+        let v22 = v(h, 1, 1);
+        let mut diff = [0.0; 6];
+        for i in 0..6{
+            diff[i] = v11[i] - v22[i];
+        }
+        for row in &[v12, diff]{
+            for i in 0..6{
+                for j in 0..6{
+                    vtv[i][j] += row[i] * row[j];
+                }
+            }
+        }
+    }
+    let b = linalg::null_vector(vtv);
+    let (b11, b12, b22, b13, b23, b33) = (b[0], b[1], b[2], b[3], b[4], b[5]);
+    let denominator = b11 * b22 - b12 * b12;
+    let v0 = (b12 * b13 - b11 * b23) / denominator;
+    let lambda = b33 - (b13 * b13 + v0 * (b12 * b13 - b11 * b23)) / b11;
+    let alpha = (lambda / b11).sqrt();
+    let beta = (lambda * b11 / denominator).sqrt();
+    let u0 = -b13 * alpha * alpha / lambda;
+    if !(alpha.is_finite() && beta.is_finite() && u0.is_finite() && v0.is_finite()){
+        return Err(Error::processing("calibrate the lens", "the views of the board are degenerate, tilt the board more between shots"));
+    }
+    // skew is left out of the model
+    Ok([[alpha, 0.0, u0], [0.0, beta, v0], [0.0, 0.0, 1.0]])
+}
+
+// board pose as a rodrigues vector and a translation
+fn initial_extrinsics(k: &Mat3, h: &Mat3)->[f64; VIEW_PARAM_COUNT]{
+    let k_inv = linalg::invert3(k).unwrap_or(*k);
+    let column = |i: usize|linalg::mul3v(&k_inv, &[h[0][i], h[1][i], h[2][i]]);
+    let (h1, h2, h3) = (column(0), column(1), column(2));
+    let mut scale = 1.0 / linalg::dot3(&h1, &h1).sqrt();
+    // the board is in front of the camera
+    if h3[2] * scale < 0.0{
+        scale = -scale;
+    }
+    let r1 = [h1[0] * scale, h1[1] * scale, h1[2] * scale];
+    let r2 = [h2[0] * scale, h2[1] * scale, h2[2] * scale];
+    let t = [h3[0] * scale, h3[1] * scale, h3[2] * scale];
+    // nearest orthonormal frame
+    let r1 = linalg::normalize3(r1).unwrap_or([1.0, 0.0, 0.0]);
+    let d = linalg::dot3(&r1, &r2);
+    let r2 = linalg::normalize3([r2[0] - d * r1[0], r2[1] - d * r1[1], r2[2] - d * r1[2]]).unwrap_or([0.0, 1.0, 0.0]);
+    let r3 = linalg::cross3(&r1, &r2);
+    let r = [[r1[0], r2[0], r3[0]], [r1[1], r2[1], r3[1]], [r1[2], r2[2], r3[2]]];
+    let w = rotation_to_vector(&r);
+    [w[0], w[1], w[2], t[0], t[1], t[2]]
+}
+
+fn vector_to_rotation(w: &[f64])->Mat3{
+    let theta = (w[0] * w[0] + w[1] * w[1] + w[2] * w[2]).sqrt();
+    if theta < 1e-12{
+        return [[1.0, -w[2], w[1]], [w[2], 1.0, -w[0]], [-w[1], w[0], 1.0]];
+    }
+    let k = [w[0] / theta, w[1] / theta, w[2] / theta];
+    let (s, c) = theta.sin_cos();
+    let mut r = [[0.0; 3]; 3];
+    let skew = [[0.0, -k[2], k[1]], [k[2], 0.0, -k[0]], [-k[1], k[0], 0.0]];
+    for i in 0..3{
+        for j in 0..3{
+            let identity = if i == j { 1.0 } else { 0.0 };
+            r[i][j] = identity * c + (1.0 - c) * k[i] * k[j] + s * skew[i][j];
+        }
+    }
+    r
+}
+
+fn rotation_to_vector(r: &Mat3)->[f64; 3]{
+    let cos = ((r[0][0] + r[1][1] + r[2][2] - 1.0) * 0.5).clamp(-1.0, 1.0);
+    let theta = cos.acos();
+    if theta < 1e-9{
+        return [0.0; 3];
+    }
+    let s = theta.sin();
+    if s < 1e-6{
+        // half turn, the axis comes from the diagonal
+        let mut k = [
+            ((r[0][0] + 1.0) * 0.5).max(0.0).sqrt(),
+            ((r[1][1] + 1.0) * 0.5).max(0.0).sqrt(),
+            ((r[2][2] + 1.0) * 0.5).max(0.0).sqrt(),
+        ];
+        if r[0][1] < 0.0 { k[1] = -k[1]; }
+        if r[0][2] < 0.0 { k[2] = -k[2]; }
+        return [k[0] * theta, k[1] * theta, k[2] * theta];
+    }
+    let f = theta / (2.0 * s);
+    [(r[2][1] - r[1][2]) * f, (r[0][2] - r[2][0]) * f, (r[1][0] - r[0][1]) * f]
+}
+
+fn project(intrinsics: &[f64], view: &[f64], x: f64, y: f64)->(f64, f64){
+    let r = vector_to_rotation(&view[0..3]);
+    let p = linalg::mul3v(&r, &[x, y, 0.0]);
+    let (px, py, pz) = (p[0] + view[3], p[1] + view[4], p[2] + view[5]);
+    let (x, y) = (px / pz, py / pz);
+    let (k1, k2, p1, p2, k3) = (intrinsics[4], intrinsics[5], intrinsics[6], intrinsics[7], intrinsics[8]);
+    let r2 = x * x + y * y;
+    let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+    let xd = x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x);
+    let yd = y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y;
+    (intrinsics[0] * xd + intrinsics[2], intrinsics[1] * yd + intrinsics[3])
+}
+
+fn residuals(params: &[f64], views: &[Vec<(f64, f64)>], object: &[(f64, f64)])->Vec<f64>{
+    let mut r = Vec::with_capacity(views.len() * object.len() * 2);
+    for (i, view) in views.iter().enumerate(){
+        let pose = &params[INTRINSIC_COUNT + i * VIEW_PARAM_COUNT..INTRINSIC_COUNT + (i + 1) * VIEW_PARAM_COUNT];
+        for (o, p) in object.iter().zip(view.iter()){
+            let (u, v) = project(&params[0..INTRINSIC_COUNT], pose, o.0, o.1);
+            r.push(u - p.0);
+            r.push(v - p.1);
+        }
+    }
+    r
+}
+
+// levenberg-marquardt with a forward difference jacobian, returns the rms error per point
+fn refine(params: &mut Vec<f64>, views: &[Vec<(f64, f64)>], object: &[(f64, f64)])->f64{
+    let n = params.len();
+    let mut r = residuals(params, views, object);
+    let mut cost: f64 = r.iter().map(|v|v * v).sum();
+    let mut lambda = 1e-3;
+    for _ in 0..MAX_ITERATIONS{
+        let mut jacobian = Vec::with_capacity(n);
+        for k in 0..n{
+            let step = 1e-6 * params[k].abs().max(1.0);
+            let mut shifted = params.clone();
+            shifted[k] += step;
+            let rk = residuals(&shifted, views, object);
+            jacobian.push(rk.iter().zip(r.iter()).map(|(a, b)|(a - b) / step).collect::<Vec<f64>>());
+        }
+        let mut jtj = vec![vec![0.0; n]; n];
+        let mut jtr = vec![0.0; n];
+        for a in 0..n{
+            for b in a..n{
+                let v: f64 = jacobian[a].iter().zip(jacobian[b].iter()).map(|(x, y)|x * y).sum();
+                jtj[a][b] = v;
+                jtj[b][a] = v;
+            }
+            jtr[a] = -jacobian[a].iter().zip(r.iter()).map(|(x, y)|x * y).sum::<f64>();
+        }
+        let mut improved = false;
+        while lambda < 1e10{
+            let mut damped = jtj.clone();
+            for a in 0..n{
+                damped[a][a] += lambda * jtj[a][a].max(1e-12);
+            }
+            if let Some(delta) = linalg::solve(damped, jtr.clone()){
+                let candidate: Vec<f64> = params.iter().zip(delta.iter()).map(|(p, d)|p + d).collect();
+                let rc = residuals(&candidate, views, object);
+                let candidate_cost: f64 = rc.iter().map(|v|v * v).sum();
+                if candidate_cost.is_finite() && candidate_cost < cost{
+                    let relative = (cost - candidate_cost) / cost.max(1e-300);
+                    *params = candidate;
+                    r = rc;
+                    cost = candidate_cost;
+                    lambda = (lambda * 0.1).max(1e-12);
+                    improved = relative > 1e-12;
+                    break;
+                }
+            }
+            lambda *= 10.0;
+        }
+        if !improved{
+            break;
+        }
+    }
+    (cost / (views.len() * object.len()) as f64).sqrt()
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::Pattern;
+
+    const INTRINSICS: [f64; INTRINSIC_COUNT] = [800.0, 780.0, 320.0, 240.0, -0.1, 0.02, 0.001, -0.002, 0.0];
+
+    // object seen from a few tilted poses, projected through INTRINSICS
+    fn board_views(object: &[(f64, f64)])->Vec<Vec<(f64, f64)>>{
+        let poses = [
+            [0.2, 0.1, 0.0, -4.0, -2.5, 12.0],
+            [-0.15, 0.25, 0.05, -3.5, -3.0, 14.0],
+            [0.1, -0.3, -0.1, -4.5, -2.0, 11.0],
+            [0.3, 0.05, 0.2, -4.0, -3.5, 13.0],
+            [-0.25, -0.1, -0.15, -3.0, -2.5, 12.5],
+        ];
+        poses.iter()
+            .map(|pose|object.iter().map(|o|project(&INTRINSICS, pose, o.0, o.1)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn calibrate_recovers_a_known_camera(){
+        let object = Pattern::default().object_points();
+        let views = board_views(&object);
+        let intrinsics = calibrate(&views, &object).unwrap();
+        let expected = [INTRINSICS[0], 0.0, INTRINSICS[2], 0.0, INTRINSICS[1], INTRINSICS[3], 0.0, 0.0, 1.0];
+        for (a, b) in intrinsics.matrix.iter().zip(expected.iter()){
+            assert!((a - b).abs() < 1e-3, "{:?} != {:?}", intrinsics.matrix, expected);
+        }
+        for (a, b) in intrinsics.dist.iter().zip(INTRINSICS[4..].iter()){
+            assert!((a - b).abs() < 1e-4, "{:?} != {:?}", intrinsics.dist, &INTRINSICS[4..]);
+        }
+        assert!(intrinsics.error < 1e-6, "error {}", intrinsics.error);
+    }
+
+    #[test]
+    fn calibrate_needs_3_views(){
+        let object = Pattern::default().object_points();
+        let views = board_views(&object);
+        assert!(matches!(calibrate(&views[..2], &object), Err(Error::Processing{..})));
+    }
+
+    #[test]
+    fn rotation_vector_round_trip(){
+        for w in [[0.0, 0.0, 0.0], [0.3, -0.2, 0.1], [0.0, 0.0, 3.0], [1.0, 2.0, -0.5]].iter(){
+            let back = rotation_to_vector(&vector_to_rotation(w));
+            for (a, b) in back.iter().zip(w.iter()){
+                assert!((a - b).abs() < 1e-9, "{:?} != {:?}", back, w);
+            }
+        }
+    }
+}
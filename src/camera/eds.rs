@@ -0,0 +1,225 @@
+use std::path::Path;
+use std::sync::mpsc;
+use edsdk::wrap;
+use super::{CameraBackend, convert_iso, convert_av, convert_tv, iso_values, av_values, tv_values};
+use super::super::error::{Error, Result};
+
+// a call to run on the camera thread
+type Call = Box<dyn FnOnce(&mut EdsdkCamera) + Send>;
+
+// Canon bodies through the EDSDK.
+// the sdk handles are not Send, they live on a thread of their own and every call is sent there.
+pub struct EdsdkBackend{
+    calls: mpsc::Sender<Call>,
+}
+
+impl EdsdkBackend{
+    pub fn new()->Result<Self>{
+        let context = "initialize the EDSDK";
+        let (calls, received) = mpsc::channel::<Call>();
+        let (started, start) = mpsc::channel();
+        std::thread::spawn(move ||{
+            let mut camera = match EdsdkCamera::new(){
+                Ok(camera)=>{
+                    let _ = started.send(Ok(()));
+                    camera
+                }
+                Err(error)=>{
+                    let _ = started.send(Err(error));
+                    return;
+                }
+            };
+            // ends once the backend is dropped, the session is closed on this thread
+            for call in received{
+                call(&mut camera);
+            }
+        });
+        start.recv().map_err(|_|Error::camera(context, "the camera thread stopped"))??;
+        Ok(EdsdkBackend{calls})
+    }
+    // run f on the camera thread and wait for its result
+    fn call<T, F>(&self, context: &str, f: F)->Result<T>
+        where T: Send + 'static, F: FnOnce(&mut EdsdkCamera)->Result<T> + Send + 'static
+    {
+        let (reply, answer) = mpsc::channel();
+        self.calls.send(Box::new(move |camera|{
+            let _ = reply.send(f(camera));
+        })).map_err(|_|Error::camera(context, "the camera thread stopped"))?;
+        answer.recv().map_err(|_|Error::camera(context, "the camera thread stopped"))?
+    }
+}
+
+impl CameraBackend for EdsdkBackend{
+    fn list_devices(&mut self)->Vec<String>{
+        self.call("list the cameras", |camera|Ok(camera.list_devices())).unwrap_or_default()
+    }
+    fn open_session(&mut self, index: usize)->Result<String>{
+        self.call("open the camera session", move |camera|camera.open_session(index))
+    }
+    fn close_session(&mut self){
+        let _ = self.call("close the camera session", |camera|{
+            camera.close_session();
+            Ok(())
+        });
+    }
+    fn is_session_open(&self)->bool{
+        self.call("check the camera session", |camera|Ok(camera.is_session_open())).unwrap_or(false)
+    }
+    fn set_iso(&mut self, iso: u32)->Result<()>{
+        self.call("set the iso", move |camera|camera.set_iso(iso))
+    }
+    fn get_iso(&self)->Result<u32>{
+        self.call("get the iso", |camera|camera.get_iso())
+    }
+    fn set_av(&mut self, av: &str)->Result<()>{
+        let av = av.to_string();
+        self.call("set the aperture", move |camera|camera.set_av(&av))
+    }
+    fn get_av(&self)->Result<String>{
+        self.call("get the aperture", |camera|camera.get_av())
+    }
+    fn set_tv(&mut self, tv: &str)->Result<()>{
+        let tv = tv.to_string();
+        self.call("set the shutter speed", move |camera|camera.set_tv(&tv))
+    }
+    fn get_tv(&self)->Result<String>{
+        self.call("get the shutter speed", |camera|camera.get_tv())
+    }
+    fn supported_iso(&self)->Result<Vec<u32>>{
+        self.call("get the iso speeds of the camera", |camera|camera.supported_iso())
+    }
+    fn supported_av(&self)->Result<Vec<String>>{
+        self.call("get the apertures of the camera", |camera|camera.supported_av())
+    }
+    fn supported_tv(&self)->Result<Vec<String>>{
+        self.call("get the shutter speeds of the camera", |camera|camera.supported_tv())
+    }
+    fn capture(&mut self, file_name: &Path)->Result<()>{
+        let file_name = file_name.to_path_buf();
+        self.call("take a picture", move |camera|camera.capture(&file_name))
+    }
+}
+
+// the body answers an unavailable value with a bare error code, name the value instead
+fn check_supported<V: PartialEq>(value: &V, supported: &[V], context: &str, label: &str)->Result<()>{
+    if supported.contains(value){
+        Ok(())
+    }
+    else{
+        Err(Error::camera(context, &format!("the camera does not accept {} in its current mode", label)))
+    }
+}
+
+// the sdk state, owned by the camera thread
+struct EdsdkCamera{
+    library: wrap::Library,
+    devices: Vec<Option<wrap::Camera>>,
+    camera_device: Option<wrap::Camera>,
+    camera_session: Option<wrap::Session>,
+    // values last accepted by the body
+    iso: u32,
+    av: String,
+    tv: String,
+}
+
+impl EdsdkCamera{
+    fn new()->Result<Self>{
+        let library = wrap::Library::initialize().map_err(|e|Error::camera("initialize the EDSDK", &format!("{:?}", e)))?;
+        Ok(EdsdkCamera{
+            library,
+            devices: Vec::new(),
+            camera_device: None,
+            camera_session: None,
+            iso: 100,
+            av: "4.0".to_string(),
+            tv: "1/15".to_string(),
+        })
+    }
+    fn session(&self, context: &str)->Result<&wrap::Session>{
+        self.camera_session.as_ref().ok_or_else(||Error::camera(context, "no camera session is open"))
+    }
+    fn close_session(&mut self){
+        self.camera_session = None;
+        self.camera_device = None;
+    }
+    fn list_devices(&mut self)->Vec<String>{
+        self.devices = self.library.get_device_list();
+        self.devices.iter().map(|dev|{
+            dev.as_ref()
+                .and_then(|dev|dev.get_device_info().ok())
+                .map(|info|info.description)
+                .unwrap_or_default()
+        }).collect()
+    }
+    fn open_session(&mut self, index: usize)->Result<String>{
+        let context = "open the camera session";
+        self.close_session();
+        if index >= self.devices.len(){
+            self.list_devices();
+        }
+        if index >= self.devices.len(){
+            return Err(Error::camera(context, "no camera is connected"));
+        }
+        let device = self.devices[index].take().ok_or_else(||Error::camera(context, "the camera is already in use"))?;
+        let info = device.get_device_info().map_err(|e|Error::camera(context, &format!("no device info: {:?}", e)))?;
+        let session = device.open_session().map_err(|e|Error::camera(context, &format!("{:?}", e)))?;
+        self.camera_device = Some(device);
+        self.camera_session = Some(session);
+        Ok(info.description)
+    }
+    fn is_session_open(&self)->bool{
+        self.camera_session.is_some()
+    }
+    fn set_iso(&mut self, iso: u32)->Result<()>{
+        let context = "set the iso";
+        check_supported(&iso, &self.supported_iso()?, context, &format!("ISO {}", iso))?;
+        self.session(context)?.set_iso_speed(convert_iso(iso)?).map_err(|e|Error::camera(context, &format!("{:?}", e)))?;
+        self.iso = iso;
+        Ok(())
+    }
+    fn get_iso(&self)->Result<u32>{
+        self.session("get the iso")?;
+        Ok(self.iso)
+    }
+    fn set_av(&mut self, av: &str)->Result<()>{
+        let context = "set the aperture";
+        check_supported(&av.to_string(), &self.supported_av()?, context, &format!("f/{}", av))?;
+        self.session(context)?.set_av(convert_av(av)?).map_err(|e|Error::camera(context, &format!("{:?}", e)))?;
+        self.av = av.to_string();
+        Ok(())
+    }
+    fn get_av(&self)->Result<String>{
+        self.session("get the aperture")?;
+        Ok(self.av.clone())
+    }
+    fn set_tv(&mut self, tv: &str)->Result<()>{
+        let context = "set the shutter speed";
+        check_supported(&tv.to_string(), &self.supported_tv()?, context, tv)?;
+        self.session(context)?.set_tv(convert_tv(tv)?).map_err(|e|Error::camera(context, &format!("{:?}", e)))?;
+        self.tv = tv.to_string();
+        Ok(())
+    }
+    fn get_tv(&self)->Result<String>{
+        self.session("get the shutter speed")?;
+        Ok(self.tv.clone())
+    }
+    fn supported_iso(&self)->Result<Vec<u32>>{
+        let context = "get the iso speeds of the camera";
+        let desc = self.session(context)?.get_iso_speed_desc().map_err(|e|Error::camera(context, &format!("{:?}", e)))?;
+        Ok(iso_values(&desc))
+    }
+    fn supported_av(&self)->Result<Vec<String>>{
+        let context = "get the apertures of the camera";
+        let desc = self.session(context)?.get_av_desc().map_err(|e|Error::camera(context, &format!("{:?}", e)))?;
+        Ok(av_values(&desc))
+    }
+    fn supported_tv(&self)->Result<Vec<String>>{
+        let context = "get the shutter speeds of the camera";
+        let desc = self.session(context)?.get_tv_desc().map_err(|e|Error::camera(context, &format!("{:?}", e)))?;
+        Ok(tv_values(&desc))
+    }
+    fn capture(&mut self, file_name: &Path)->Result<()>{
+        let context = "take a picture";
+        self.session(context)?.take_picture(file_name).map_err(|e|Error::camera(context, &format!("{:?}", e)))
+    }
+}
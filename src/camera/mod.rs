@@ -1,120 +1,259 @@
-//use edsdk::wrap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use super::error::{Error, Result};
+#[cfg(feature = "edsdk")]
 use edsdk::types;
 
-#[allow(dead_code)]
-pub fn convert_iso(iso_str: u32)->types::ISOSpeed{
-    match iso_str{
-        50=>{types::ISOSpeed::ISO50}
-        100=>{types::ISOSpeed::ISO100}
-        200=>{types::ISOSpeed::ISO200}
-        400=>{types::ISOSpeed::ISO400}
-        800=>{types::ISOSpeed::ISO800}
-        1600=>{types::ISOSpeed::ISO160}
-        3200=>{types::ISOSpeed::ISO320}
-        _=>{types::ISOSpeed::ISO100}
-    }
+#[cfg(feature = "edsdk")]
+mod eds;
+#[cfg(feature = "edsdk")]
+pub use eds::EdsdkBackend;
+mod simulated;
+pub use simulated::SimulatedBackend;
+mod unavailable;
+pub use unavailable::UnavailableBackend;
+
+// environment variable pointing at a directory of frames for the simulated camera
+pub const SIMULATED_CAMERA_ENV: &str = "TEX_SHOOTER_SIMULATED_CAMERA";
+
+// a camera the application can enumerate, open and shoot with.
+// Send so that capture jobs can drive it from a worker thread.
+pub trait CameraBackend: Send{
+    // descriptions of the connected devices
+    fn list_devices(&mut self)->Vec<String>;
+    // open a session on the device at index and return its description
+    fn open_session(&mut self, index: usize)->Result<String>;
+    fn close_session(&mut self);
+    fn is_session_open(&self)->bool;
+    fn set_iso(&mut self, iso: u32)->Result<()>;
+    fn get_iso(&self)->Result<u32>;
+    fn set_av(&mut self, av: &str)->Result<()>;
+    fn get_av(&self)->Result<String>;
+    fn set_tv(&mut self, tv: &str)->Result<()>;
+    fn get_tv(&self)->Result<String>;
+    // values the body accepts in its current mode, asked from the body each time
+    fn supported_iso(&self)->Result<Vec<u32>>;
+    fn supported_av(&self)->Result<Vec<String>>;
+    fn supported_tv(&self)->Result<Vec<String>>;
+    // shoot a frame and store it as file_name
+    fn capture(&mut self, file_name: &Path)->Result<()>;
 }
 
-#[allow(dead_code)]
-pub fn convert_av(av_str: &str)->types::ApertureValue{
-    match av_str{
-        "1.0"=>{types::ApertureValue::Av1_0}
-        "1.1"=>{types::ApertureValue::Av1_1}
-        "1.2"=>{types::ApertureValue::Av1_2}
-        "1.4"=>{types::ApertureValue::Av1_4}
-        "1.6"=>{types::ApertureValue::Av1_6}
-        "1.8"=>{types::ApertureValue::Av1_8}
-        "2.0"=>{types::ApertureValue::Av2_0}
-        "2.2"=>{types::ApertureValue::Av2_2}
-        "2.5"=>{types::ApertureValue::Av2_5}
-        "2.8"=>{types::ApertureValue::Av2_8}
-        "3.2"=>{types::ApertureValue::Av3_2}
-        "3.5"=>{types::ApertureValue::Av3_5}
-        "4.0"=>{types::ApertureValue::Av4_0}
-        "4.5"=>{types::ApertureValue::Av4_5}
-        "5.0"=>{types::ApertureValue::Av5_0}
-        "5.6"=>{types::ApertureValue::Av5_6}
-        "6.3"=>{types::ApertureValue::Av6_3}
-        "6.7"=>{types::ApertureValue::Av6_7}
-        "7.1"=>{types::ApertureValue::Av7_1}
-        "8.0"=>{types::ApertureValue::Av8_0}
-        "9.0"=>{types::ApertureValue::Av9_0}
-        "9.5"=>{types::ApertureValue::Av9_5}
-        "10.0"=>{types::ApertureValue::Av10_0}
-        "11.0"=>{types::ApertureValue::Av11_0}
-        "13.0"=>{types::ApertureValue::Av13_0}
-        "14.0"=>{types::ApertureValue::Av14_0}
-        "16.0"=>{types::ApertureValue::Av16_0}
-        "18.0"=>{types::ApertureValue::Av18_0}
-        "19.0"=>{types::ApertureValue::Av19_0}
-        "20.0"=>{types::ApertureValue::Av20_0}
-        "22.0"=>{types::ApertureValue::Av22_0}
-        "25.0"=>{types::ApertureValue::Av25_0}
-        "27.0"=>{types::ApertureValue::Av27_0}
-        "29.0"=>{types::ApertureValue::Av29_0}
-        "32.0"=>{types::ApertureValue::Av32_0}
-        _=>{types::ApertureValue::Av4_0}
+// the camera shared between the ui thread and jobs
+pub type SharedCamera = Arc<Mutex<Box<dyn CameraBackend>>>;
+
+// the simulated camera when SIMULATED_CAMERA_ENV is set, EDSDK otherwise
+pub fn create_backend()->Result<Box<dyn CameraBackend>>{
+    if let Some(dir) = std::env::var_os(SIMULATED_CAMERA_ENV){
+        return Ok(Box::new(SimulatedBackend::new(Path::new(&dir))));
     }
+    create_default_backend()
 }
 
-#[allow(dead_code)]
-pub fn convert_tv(tv_str: &str)->types::ShutterSpeed{
-    match tv_str{
-        "3"=>{types::ShutterSpeed::Tv3}
-        "2.5"=>{types::ShutterSpeed::Tv2_5}
-        "2"=>{types::ShutterSpeed::Tv2}
-        "1.6"=>{types::ShutterSpeed::Tv1_6}
-        "1.5"=>{types::ShutterSpeed::Tv1_5}
-        "1.3"=>{types::ShutterSpeed::Tv1_3}
-        "1"=>{types::ShutterSpeed::Tv1}
-        "0.8"=>{types::ShutterSpeed::Tv0_8}
-        "0.7"=>{types::ShutterSpeed::Tv0_7}
-        "0.6"=>{types::ShutterSpeed::Tv0_6}
-        "0.5"=>{types::ShutterSpeed::Tv0_5}
-        "0.4"=>{types::ShutterSpeed::Tv0_4}
-        "0.3"=>{types::ShutterSpeed::Tv0_3}
-        "1/4"=>{types::ShutterSpeed::Tv1_4th}
-        "1/5"=>{types::ShutterSpeed::Tv1_5th}
-        "1/6"=>{types::ShutterSpeed::Tv1_6th}
-        "1/8"=>{types::ShutterSpeed::Tv1_8th}
-        "1/10"=>{types::ShutterSpeed::Tv1_10th}
-        "1/13"=>{types::ShutterSpeed::Tv1_13th}
-        "1/15"=>{types::ShutterSpeed::Tv1_15th}
-        "1/20"=>{types::ShutterSpeed::Tv1_20th}
-        "1/25"=>{types::ShutterSpeed::Tv1_25th}
-        "1/30"=>{types::ShutterSpeed::Tv1_30th}
-        "1/40"=>{types::ShutterSpeed::Tv1_40th}
-        "1/45"=>{types::ShutterSpeed::Tv1_45th}
-        "1/50"=>{types::ShutterSpeed::Tv1_50th}
-        "1/60"=>{types::ShutterSpeed::Tv1_60th}
-        "1/80"=>{types::ShutterSpeed::Tv1_80th}
-        "1/90"=>{types::ShutterSpeed::Tv1_90th}
-        "1/100"=>{types::ShutterSpeed::Tv1_100th}
-        "1/125"=>{types::ShutterSpeed::Tv1_125th}
-        "1/160"=>{types::ShutterSpeed::Tv1_160th}
-        "1/180"=>{types::ShutterSpeed::Tv1_180th}
-        "1/200"=>{types::ShutterSpeed::Tv1_200th}
-        "1/250"=>{types::ShutterSpeed::Tv1_250th}
-        _=>{types::ShutterSpeed::Tv1_15th}
-    }
+#[cfg(feature = "edsdk")]
+fn create_default_backend()->Result<Box<dyn CameraBackend>>{
+    let backend = EdsdkBackend::new()?;
+    Ok(Box::new(backend))
+}
+
+#[cfg(not(feature = "edsdk"))]
+fn create_default_backend()->Result<Box<dyn CameraBackend>>{
+    // frames next to the executable
+    let mut path = std::env::current_exe().map_err(|e|Error::io(Path::new("tex_shooter"), "find the executable", e))?;
+    path.pop();
+    path.push("simulated");
+    Ok(Box::new(SimulatedBackend::new(&path)))
+}
+
+// every value the EDSDK has a code for, the descriptor of the body picks the ones it offers.
+// the labels are those of the exposure tables.
+#[cfg(feature = "edsdk")]
+const ISO_SPEEDS: &[(u32, types::ISOSpeed)] = &[
+    (50, types::ISOSpeed::ISO50),
+    (100, types::ISOSpeed::ISO100),
+    (125, types::ISOSpeed::ISO125),
+    (160, types::ISOSpeed::ISO160),
+    (200, types::ISOSpeed::ISO200),
+    (250, types::ISOSpeed::ISO250),
+    (320, types::ISOSpeed::ISO320),
+    (400, types::ISOSpeed::ISO400),
+    (500, types::ISOSpeed::ISO500),
+    (640, types::ISOSpeed::ISO640),
+    (800, types::ISOSpeed::ISO800),
+    (1000, types::ISOSpeed::ISO1000),
+    (1250, types::ISOSpeed::ISO1250),
+    (1600, types::ISOSpeed::ISO1600),
+    (2000, types::ISOSpeed::ISO2000),
+    (2500, types::ISOSpeed::ISO2500),
+    (3200, types::ISOSpeed::ISO3200),
+    (4000, types::ISOSpeed::ISO4000),
+    (5000, types::ISOSpeed::ISO5000),
+    (6400, types::ISOSpeed::ISO6400),
+    (8000, types::ISOSpeed::ISO8000),
+    (10000, types::ISOSpeed::ISO10000),
+    (12800, types::ISOSpeed::ISO12800),
+    (16000, types::ISOSpeed::ISO16000),
+    (20000, types::ISOSpeed::ISO20000),
+    (25600, types::ISOSpeed::ISO25600),
+    (32000, types::ISOSpeed::ISO32000),
+    (40000, types::ISOSpeed::ISO40000),
+    (51200, types::ISOSpeed::ISO51200),
+    (64000, types::ISOSpeed::ISO64000),
+    (80000, types::ISOSpeed::ISO80000),
+    (102400, types::ISOSpeed::ISO102400),
+];
+
+#[cfg(feature = "edsdk")]
+const APERTURE_VALUES: &[(&str, types::ApertureValue)] = &[
+    ("1.0", types::ApertureValue::Av1_0),
+    ("1.1", types::ApertureValue::Av1_1),
+    ("1.2", types::ApertureValue::Av1_2),
+    ("1.4", types::ApertureValue::Av1_4),
+    ("1.6", types::ApertureValue::Av1_6),
+    ("1.8", types::ApertureValue::Av1_8),
+    ("2.0", types::ApertureValue::Av2_0),
+    ("2.2", types::ApertureValue::Av2_2),
+    ("2.5", types::ApertureValue::Av2_5),
+    ("2.8", types::ApertureValue::Av2_8),
+    ("3.2", types::ApertureValue::Av3_2),
+    ("3.5", types::ApertureValue::Av3_5),
+    ("4.0", types::ApertureValue::Av4_0),
+    ("4.5", types::ApertureValue::Av4_5),
+    ("5.0", types::ApertureValue::Av5_0),
+    ("5.6", types::ApertureValue::Av5_6),
+    ("6.3", types::ApertureValue::Av6_3),
+    ("6.7", types::ApertureValue::Av6_7),
+    ("7.1", types::ApertureValue::Av7_1),
+    ("8.0", types::ApertureValue::Av8_0),
+    ("9.0", types::ApertureValue::Av9_0),
+    ("9.5", types::ApertureValue::Av9_5),
+    ("10.0", types::ApertureValue::Av10_0),
+    ("11.0", types::ApertureValue::Av11_0),
+    ("13.0", types::ApertureValue::Av13_0),
+    ("14.0", types::ApertureValue::Av14_0),
+    ("16.0", types::ApertureValue::Av16_0),
+    ("18.0", types::ApertureValue::Av18_0),
+    ("19.0", types::ApertureValue::Av19_0),
+    ("20.0", types::ApertureValue::Av20_0),
+    ("22.0", types::ApertureValue::Av22_0),
+    ("25.0", types::ApertureValue::Av25_0),
+    ("27.0", types::ApertureValue::Av27_0),
+    ("29.0", types::ApertureValue::Av29_0),
+    ("32.0", types::ApertureValue::Av32_0),
+    ("36.0", types::ApertureValue::Av36_0),
+    ("38.0", types::ApertureValue::Av38_0),
+    ("40.0", types::ApertureValue::Av40_0),
+    ("45.0", types::ApertureValue::Av45_0),
+    ("51.0", types::ApertureValue::Av51_0),
+    ("54.0", types::ApertureValue::Av54_0),
+    ("57.0", types::ApertureValue::Av57_0),
+    ("64.0", types::ApertureValue::Av64_0),
+    ("72.0", types::ApertureValue::Av72_0),
+    ("76.0", types::ApertureValue::Av76_0),
+    ("80.0", types::ApertureValue::Av80_0),
+    ("91.0", types::ApertureValue::Av91_0),
+];
+
+#[cfg(feature = "edsdk")]
+const SHUTTER_SPEEDS: &[(&str, types::ShutterSpeed)] = &[
+    ("30", types::ShutterSpeed::Tv30),
+    ("25", types::ShutterSpeed::Tv25),
+    ("20", types::ShutterSpeed::Tv20),
+    ("15", types::ShutterSpeed::Tv15),
+    ("13", types::ShutterSpeed::Tv13),
+    ("10", types::ShutterSpeed::Tv10),
+    ("8", types::ShutterSpeed::Tv8),
+    ("6", types::ShutterSpeed::Tv6),
+    ("5", types::ShutterSpeed::Tv5),
+    ("4", types::ShutterSpeed::Tv4),
+    ("3.2", types::ShutterSpeed::Tv3_2),
+    ("3", types::ShutterSpeed::Tv3),
+    ("2.5", types::ShutterSpeed::Tv2_5),
+    ("2", types::ShutterSpeed::Tv2),
+    ("1.6", types::ShutterSpeed::Tv1_6),
+    ("1.5", types::ShutterSpeed::Tv1_5),
+    ("1.3", types::ShutterSpeed::Tv1_3),
+    ("1", types::ShutterSpeed::Tv1),
+    ("0.8", types::ShutterSpeed::Tv0_8),
+    ("0.7", types::ShutterSpeed::Tv0_7),
+    ("0.6", types::ShutterSpeed::Tv0_6),
+    ("0.5", types::ShutterSpeed::Tv0_5),
+    ("0.4", types::ShutterSpeed::Tv0_4),
+    ("0.3", types::ShutterSpeed::Tv0_3),
+    ("1/4", types::ShutterSpeed::Tv1_4th),
+    ("1/5", types::ShutterSpeed::Tv1_5th),
+    ("1/6", types::ShutterSpeed::Tv1_6th),
+    ("1/8", types::ShutterSpeed::Tv1_8th),
+    ("1/10", types::ShutterSpeed::Tv1_10th),
+    ("1/13", types::ShutterSpeed::Tv1_13th),
+    ("1/15", types::ShutterSpeed::Tv1_15th),
+    ("1/20", types::ShutterSpeed::Tv1_20th),
+    ("1/25", types::ShutterSpeed::Tv1_25th),
+    ("1/30", types::ShutterSpeed::Tv1_30th),
+    ("1/40", types::ShutterSpeed::Tv1_40th),
+    ("1/45", types::ShutterSpeed::Tv1_45th),
+    ("1/50", types::ShutterSpeed::Tv1_50th),
+    ("1/60", types::ShutterSpeed::Tv1_60th),
+    ("1/80", types::ShutterSpeed::Tv1_80th),
+    ("1/90", types::ShutterSpeed::Tv1_90th),
+    ("1/100", types::ShutterSpeed::Tv1_100th),
+    ("1/125", types::ShutterSpeed::Tv1_125th),
+    ("1/160", types::ShutterSpeed::Tv1_160th),
+    ("1/180", types::ShutterSpeed::Tv1_180th),
+    ("1/200", types::ShutterSpeed::Tv1_200th),
+    ("1/250", types::ShutterSpeed::Tv1_250th),
+    ("1/320", types::ShutterSpeed::Tv1_320th),
+    ("1/350", types::ShutterSpeed::Tv1_350th),
+    ("1/400", types::ShutterSpeed::Tv1_400th),
+    ("1/500", types::ShutterSpeed::Tv1_500th),
+    ("1/640", types::ShutterSpeed::Tv1_640th),
+    ("1/750", types::ShutterSpeed::Tv1_750th),
+    ("1/800", types::ShutterSpeed::Tv1_800th),
+    ("1/1000", types::ShutterSpeed::Tv1_1000th),
+    ("1/1250", types::ShutterSpeed::Tv1_1250th),
+    ("1/1500", types::ShutterSpeed::Tv1_1500th),
+    ("1/1600", types::ShutterSpeed::Tv1_1600th),
+    ("1/2000", types::ShutterSpeed::Tv1_2000th),
+    ("1/2500", types::ShutterSpeed::Tv1_2500th),
+    ("1/3000", types::ShutterSpeed::Tv1_3000th),
+    ("1/3200", types::ShutterSpeed::Tv1_3200th),
+    ("1/4000", types::ShutterSpeed::Tv1_4000th),
+    ("1/5000", types::ShutterSpeed::Tv1_5000th),
+    ("1/6000", types::ShutterSpeed::Tv1_6000th),
+    ("1/6400", types::ShutterSpeed::Tv1_6400th),
+    ("1/8000", types::ShutterSpeed::Tv1_8000th),
+];
+
+#[cfg(feature = "edsdk")]
+pub fn convert_iso(iso: u32)->Result<types::ISOSpeed>{
+    ISO_SPEEDS.iter().find(|(value, _)|*value == iso).map(|(_, speed)|*speed)
+        .ok_or_else(||Error::validation("set the iso", &format!("ISO {} can not be set on the camera", iso)))
+}
+
+#[cfg(feature = "edsdk")]
+pub fn convert_av(av_str: &str)->Result<types::ApertureValue>{
+    APERTURE_VALUES.iter().find(|(label, _)|*label == av_str).map(|(_, value)|*value)
+        .ok_or_else(||Error::validation("set the aperture", &format!("f/{} can not be set on the camera", av_str)))
+}
+
+#[cfg(feature = "edsdk")]
+pub fn convert_tv(tv_str: &str)->Result<types::ShutterSpeed>{
+    SHUTTER_SPEEDS.iter().find(|(label, _)|*label == tv_str).map(|(_, speed)|*speed)
+        .ok_or_else(||Error::validation("set the shutter speed", &format!("{} can not be set on the camera", tv_str)))
+}
+
+// the values of a property descriptor we have a name for, in the order of the descriptor
+#[cfg(feature = "edsdk")]
+pub fn iso_values(desc: &[types::ISOSpeed])->Vec<u32>{
+    desc.iter().filter_map(|speed|ISO_SPEEDS.iter().find(|(_, s)|s == speed).map(|(value, _)|*value)).collect()
+}
+
+#[cfg(feature = "edsdk")]
+pub fn av_values(desc: &[types::ApertureValue])->Vec<String>{
+    desc.iter().filter_map(|av|APERTURE_VALUES.iter().find(|(_, a)|a == av).map(|(label, _)|label.to_string())).collect()
 }
 
-#[allow(dead_code)]
-pub fn convert_tv_denominator(denominator: u32)->u32{
-    return match denominator{
-        15=>16,
-        30=>32,
-        60=>64,
-        125=>128,
-        250=>256,
-        500=>512,
-        1000=>1024,
-        2000=>2048,
-        4000=>4096,
-        8000=>8192,
-        16000=>16384,
-        32000=>32768,
-        64000=>65536,
-        _=>denominator
-    };
+#[cfg(feature = "edsdk")]
+pub fn tv_values(desc: &[types::ShutterSpeed])->Vec<String>{
+    desc.iter().filter_map(|tv|SHUTTER_SPEEDS.iter().find(|(_, t)|t == tv).map(|(label, _)|label.to_string())).collect()
 }
@@ -0,0 +1,244 @@
+use std::path::{Path, PathBuf};
+use super::CameraBackend;
+use super::super::error::{Error, Result};
+use super::super::exposure::{Aperture, Increment, Iso, ShutterSpeed};
+
+// a fake camera that serves frames from a directory on disk
+pub struct SimulatedBackend{
+    frame_dir: PathBuf,
+    next_frame: usize,
+    session_open: bool,
+    // values last accepted
+    iso: u32,
+    av: String,
+    tv: String,
+}
+
+impl SimulatedBackend{
+    pub fn new(frame_dir: &Path)->Self{
+        SimulatedBackend{
+            frame_dir: frame_dir.to_path_buf(),
+            next_frame: 0,
+            session_open: false,
+            iso: 100,
+            av: "4.0".to_string(),
+            tv: "1/15".to_string(),
+        }
+    }
+    fn description(&self)->String{
+        format!("simulated camera ({})", self.frame_dir.display())
+    }
+    fn list_frames(&self)->Vec<PathBuf>{
+        let mut frames = Vec::new();
+        if let Ok(dir) = self.frame_dir.read_dir(){
+            for entry in dir.flatten(){
+                let path = entry.path();
+                let is_image = path.extension()
+                    .and_then(|ext|ext.to_str())
+                    .map(|ext|matches!(ext.to_ascii_lowercase().as_str(), "jpg" | "jpeg" | "png"))
+                    .unwrap_or(false);
+                if path.is_file() && is_image{
+                    frames.push(path);
+                }
+            }
+        }
+        frames.sort();
+        frames
+    }
+    // a frame named like the requested file wins, otherwise frames are served in turn
+    fn pick_frame(&mut self, file_name: &Path)->Result<PathBuf>{
+        let frames = self.list_frames();
+        if frames.is_empty(){
+            return Err(Error::camera("take a picture", &format!("no frames in {}", self.frame_dir.display())));
+        }
+        let stem = file_name.file_stem();
+        if let Some(frame) = frames.iter().find(|f|f.file_stem() == stem){
+            return Ok(frame.clone());
+        }
+        let frame = frames[self.next_frame % frames.len()].clone();
+        self.next_frame += 1;
+        Ok(frame)
+    }
+    fn check_session(&self, context: &str)->Result<()>{
+        if self.session_open{
+            Ok(())
+        }
+        else{
+            Err(Error::camera(context, "no camera session is open"))
+        }
+    }
+}
+
+// like a real body, a value off the list is refused
+fn check_supported<V: PartialEq>(value: &V, supported: &[V], context: &str, label: &str)->Result<()>{
+    if supported.contains(value){
+        Ok(())
+    }
+    else{
+        Err(Error::camera(context, &format!("the simulated camera does not accept {}", label)))
+    }
+}
+
+impl CameraBackend for SimulatedBackend{
+    fn list_devices(&mut self)->Vec<String>{
+        vec![self.description()]
+    }
+    fn open_session(&mut self, index: usize)->Result<String>{
+        if index != 0{
+            return Err(Error::camera("open the camera session", &format!("no camera at index {}", index)));
+        }
+        self.session_open = true;
+        Ok(self.description())
+    }
+    fn close_session(&mut self){
+        self.session_open = false;
+    }
+    fn is_session_open(&self)->bool{
+        self.session_open
+    }
+    fn set_iso(&mut self, iso: u32)->Result<()>{
+        check_supported(&iso, &self.supported_iso()?, "set the iso", &format!("ISO {}", iso))?;
+        self.iso = iso;
+        Ok(())
+    }
+    fn get_iso(&self)->Result<u32>{
+        self.check_session("get the iso")?;
+        Ok(self.iso)
+    }
+    fn set_av(&mut self, av: &str)->Result<()>{
+        check_supported(&av.to_string(), &self.supported_av()?, "set the aperture", &format!("f/{}", av))?;
+        self.av = av.to_string();
+        Ok(())
+    }
+    fn get_av(&self)->Result<String>{
+        self.check_session("get the aperture")?;
+        Ok(self.av.clone())
+    }
+    fn set_tv(&mut self, tv: &str)->Result<()>{
+        check_supported(&tv.to_string(), &self.supported_tv()?, "set the shutter speed", tv)?;
+        self.tv = tv.to_string();
+        Ok(())
+    }
+    fn get_tv(&self)->Result<String>{
+        self.check_session("get the shutter speed")?;
+        Ok(self.tv.clone())
+    }
+    // every third stop of the exposure tables
+    fn supported_iso(&self)->Result<Vec<u32>>{
+        self.check_session("get the exposure values of the camera")?;
+        Ok(Iso::series(Increment::Third).iter().map(|iso|iso.speed()).collect())
+    }
+    fn supported_av(&self)->Result<Vec<String>>{
+        self.check_session("get the exposure values of the camera")?;
+        Ok(Aperture::series(Increment::Third).iter().map(|av|av.label().to_string()).collect())
+    }
+    fn supported_tv(&self)->Result<Vec<String>>{
+        self.check_session("get the exposure values of the camera")?;
+        Ok(ShutterSpeed::series(Increment::Third).iter().map(|tv|tv.label().to_string()).collect())
+    }
+    fn capture(&mut self, file_name: &Path)->Result<()>{
+        self.check_session("take a picture")?;
+        let frame = self.pick_frame(file_name)?;
+        std::fs::copy(&frame, file_name).map_err(|e|Error::io(&frame, "take a picture", e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::super::testing::TempDir;
+
+    // frames whose contents are their names, so a copy tells which one was served
+    fn backend(dir: &TempDir, names: &[&str])->SimulatedBackend{
+        let frame_dir = dir.path().join("frames");
+        std::fs::create_dir_all(&frame_dir).unwrap();
+        for name in names{
+            std::fs::write(frame_dir.join(name), name).unwrap();
+        }
+        SimulatedBackend::new(&frame_dir)
+    }
+
+    fn served(path: &Path)->String{
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn only_index_0_opens_a_session(){
+        let dir = TempDir::new("simulated_session");
+        let mut camera = backend(&dir, &[]);
+        assert_eq!(camera.list_devices().len(), 1);
+        assert!(matches!(camera.open_session(1), Err(Error::Camera{..})));
+        assert!(!camera.is_session_open());
+        camera.open_session(0).unwrap();
+        assert!(camera.is_session_open());
+        camera.close_session();
+        assert!(!camera.is_session_open());
+        assert!(camera.get_iso().is_err());
+    }
+
+    #[test]
+    fn calls_without_a_session_fail(){
+        let dir = TempDir::new("simulated_no_session");
+        let mut camera = backend(&dir, &["a.jpg"]);
+        assert!(camera.supported_iso().is_err());
+        assert!(camera.set_av("5.6").is_err());
+        assert!(camera.capture(&dir.path().join("out.jpg")).is_err());
+    }
+
+    #[test]
+    fn capture_prefers_a_frame_named_like_the_target(){
+        let dir = TempDir::new("simulated_capture");
+        let mut camera = backend(&dir, &["b.png", "a.jpg", "gray.jpg", "notes.txt"]);
+        camera.open_session(0).unwrap();
+        let out = dir.path().join("gray.jpg");
+        camera.capture(&out).unwrap();
+        assert_eq!(served(&out), "gray.jpg");
+        // the others rotate in name order, the text file is not a frame
+        let out = dir.path().join("take.jpg");
+        let mut order = Vec::new();
+        for _ in 0..4{
+            camera.capture(&out).unwrap();
+            order.push(served(&out));
+        }
+        assert_eq!(order, vec!["a.jpg", "b.png", "gray.jpg", "a.jpg"]);
+    }
+
+    #[test]
+    fn capture_without_frames_fails(){
+        let dir = TempDir::new("simulated_empty");
+        let mut camera = backend(&dir, &[]);
+        camera.open_session(0).unwrap();
+        assert!(matches!(camera.capture(&dir.path().join("out.jpg")), Err(Error::Camera{..})));
+    }
+
+    #[test]
+    fn exposure_values_off_the_third_stop_list_are_refused(){
+        let dir = TempDir::new("simulated_exposure");
+        let mut camera = backend(&dir, &[]);
+        camera.open_session(0).unwrap();
+        camera.set_iso(100).unwrap();
+        camera.set_iso(125).unwrap();
+        camera.set_av("5.6").unwrap();
+        camera.set_tv("1/125").unwrap();
+        assert!(camera.set_iso(110).is_err());
+        assert!(camera.set_av("5.5").is_err());
+        assert!(camera.set_tv("1/7").is_err());
+        assert!(camera.supported_av().unwrap().contains(&"7.1".to_string()));
+    }
+
+    #[test]
+    fn getters_return_the_last_accepted_values(){
+        let dir = TempDir::new("simulated_getters");
+        let mut camera = backend(&dir, &[]);
+        camera.open_session(0).unwrap();
+        camera.set_iso(400).unwrap();
+        camera.set_av("8.0").unwrap();
+        camera.set_tv("1/60").unwrap();
+        // a refused value keeps the earlier one
+        assert!(camera.set_iso(110).is_err());
+        assert_eq!(camera.get_iso().unwrap(), 400);
+        assert_eq!(camera.get_av().unwrap(), "8.0");
+        assert_eq!(camera.get_tv().unwrap(), "1/60");
+    }
+}
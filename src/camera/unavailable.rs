@@ -0,0 +1,61 @@
+use std::path::Path;
+use super::CameraBackend;
+use super::super::error::{Error, Result};
+
+// stands in when the camera sdk could not be loaded, every call fails with the reason
+pub struct UnavailableBackend{
+    reason: String,
+}
+
+impl UnavailableBackend{
+    pub fn new(reason: &str)->Self{
+        UnavailableBackend{reason: reason.to_string()}
+    }
+    fn fail<T>(&self, context: &str)->Result<T>{
+        Err(Error::camera(context, &self.reason))
+    }
+}
+
+impl CameraBackend for UnavailableBackend{
+    fn list_devices(&mut self)->Vec<String>{
+        Vec::new()
+    }
+    fn open_session(&mut self, _index: usize)->Result<String>{
+        self.fail("open the camera session")
+    }
+    fn close_session(&mut self){
+    }
+    fn is_session_open(&self)->bool{
+        false
+    }
+    fn set_iso(&mut self, _iso: u32)->Result<()>{
+        self.fail("set the iso")
+    }
+    fn get_iso(&self)->Result<u32>{
+        self.fail("get the iso")
+    }
+    fn set_av(&mut self, _av: &str)->Result<()>{
+        self.fail("set the aperture")
+    }
+    fn get_av(&self)->Result<String>{
+        self.fail("get the aperture")
+    }
+    fn set_tv(&mut self, _tv: &str)->Result<()>{
+        self.fail("set the shutter speed")
+    }
+    fn get_tv(&self)->Result<String>{
+        self.fail("get the shutter speed")
+    }
+    fn supported_iso(&self)->Result<Vec<u32>>{
+        self.fail("get the exposure values of the camera")
+    }
+    fn supported_av(&self)->Result<Vec<String>>{
+        self.fail("get the exposure values of the camera")
+    }
+    fn supported_tv(&self)->Result<Vec<String>>{
+        self.fail("get the exposure values of the camera")
+    }
+    fn capture(&mut self, _file_name: &Path)->Result<()>{
+        self.fail("take a picture")
+    }
+}
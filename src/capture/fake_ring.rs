@@ -0,0 +1,84 @@
+// an LED ring on a pseudo terminal, to run capture sequences end to end without hardware.
+// it speaks the protocol described in light.rs and records what it was told.
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use nix::pty;
+use nix::sys::termios;
+use super::super::config::TAKE_COUNT;
+use super::super::error::{Error, Result};
+
+pub struct FakeLightRing{
+    path: PathBuf,
+    commands: Arc<Mutex<Vec<String>>>,
+    // kept open so that the device exists while the ring does
+    _slave: File,
+}
+
+impl FakeLightRing{
+    pub fn start()->Result<Self>{
+        let context = "start the fake light ring";
+        let pair = pty::openpty(None, None).map_err(|e|Error::capture(context, &format!("no pseudo terminal: {}", e)))?;
+        // SAFETY: openpty just returned both descriptors and nothing else owns them
+        let (master, slave) = unsafe { (File::from_raw_fd(pair.master), File::from_raw_fd(pair.slave)) };
+        make_raw(pair.slave)?;
+        let path = nix::unistd::ttyname(pair.slave).map_err(|e|Error::capture(context, &format!("the pseudo terminal has no name: {}", e)))?;
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let received = commands.clone();
+        std::thread::spawn(move ||{
+            let mut writer = match master.try_clone(){
+                Ok(writer)=>writer,
+                Err(_)=>return,
+            };
+            // the read fails once the ring is dropped and no one holds the slave side
+            for line in BufReader::new(master).lines(){
+                let line = match line{
+                    Ok(line)=>line.trim().to_string(),
+                    Err(_)=>break,
+                };
+                if line.is_empty(){
+                    continue;
+                }
+                let answer = answer(&line);
+                received.lock().unwrap().push(line);
+                if writer.write_all(answer.as_bytes()).is_err(){
+                    break;
+                }
+            }
+        });
+        Ok(FakeLightRing{path, commands, _slave: slave})
+    }
+    // device to open with SerialLights
+    pub fn path(&self)->&Path{
+        &self.path
+    }
+    // every command received so far
+    pub fn commands(&self)->Vec<String>{
+        self.commands.lock().unwrap().clone()
+    }
+}
+
+// no echo and no line editing, like a real serial device
+fn make_raw(fd: RawFd)->Result<()>{
+    let error = |e: nix::Error|Error::capture("start the fake light ring", &e.to_string());
+    let mut attributes = termios::tcgetattr(fd).map_err(error)?;
+    termios::cfmakeraw(&mut attributes);
+    termios::tcsetattr(fd, termios::SetArg::TCSANOW, &attributes).map_err(error)
+}
+
+fn answer(command: &str)->String{
+    let mut words = command.split_whitespace();
+    let valid = match (words.next(), words.next(), words.next()){
+        (Some("PING"), None, _) | (Some("ALL"), None, _) | (Some("OFF"), None, _)=>true,
+        (Some("DIR"), Some(index), None)=>index.parse::<usize>().map(|index|index < TAKE_COUNT).unwrap_or(false),
+        _=>false,
+    };
+    if valid{
+        "OK\n".to_string()
+    }
+    else{
+        format!("ERR unknown command {}\n", command)
+    }
+}
@@ -0,0 +1,164 @@
+// switching the light direction between takes.
+//
+// serial protocol of the LED ring, one ascii command per line ('\n'),
+// every command is answered with a line "OK" or "ERR <reason>":
+//   PING       is the ring there
+//   ALL        every LED on, for the gray frame
+//   DIR <n>    only the LEDs of light direction n (0..TAKE_COUNT) on
+//   OFF        every LED off
+use std::io::{BufRead, BufReader, Write};
+use std::time::Duration;
+use super::Shot;
+use super::super::config::{LightControllerKind, LightSettings, TAKE_COUNT};
+use super::super::error::{Error, Result};
+
+// when set, the serial controller talks to a FakeLightRing on a pseudo terminal (unix only)
+pub const FAKE_LIGHT_RING_ENV: &str = "TEX_SHOOTER_FAKE_LIGHT_RING";
+
+const SERIAL_TIMEOUT: Duration = Duration::from_millis(1000);
+// an arduino resets when the port is opened and needs a moment before it answers
+const PING_ATTEMPTS: usize = 5;
+
+pub trait LightController{
+    // set the lights up for shot, called right before it is taken
+    fn prepare(&mut self, shot: Shot)->Result<()>;
+    // called once after the sequence, also when it stopped early
+    fn finish(&mut self){
+    }
+}
+
+// a fixed rig
+pub struct NoLights;
+
+impl LightController for NoLights{
+    fn prepare(&mut self, _shot: Shot)->Result<()>{
+        Ok(())
+    }
+}
+
+// asks the operator to move the light and waits for the answer
+pub struct ManualLights<F: FnMut(&str)->Result<()>>{
+    prompt: F,
+}
+
+impl<F: FnMut(&str)->Result<()>> ManualLights<F>{
+    // prompt shows the message and returns once the light is in place
+    pub fn new(prompt: F)->Self{
+        ManualLights{prompt}
+    }
+}
+
+impl<F: FnMut(&str)->Result<()>> LightController for ManualLights<F>{
+    fn prepare(&mut self, shot: Shot)->Result<()>{
+        let message = match shot{
+            Shot::Gray=>"light the gray card evenly".to_string(),
+            // same directions as processing::normal::default_light_directions
+            Shot::Take(index)=>format!("move the light to direction {} ({} degrees counterclockwise from the right)", index, index * 360 / TAKE_COUNT),
+        };
+        (self.prompt)(&message)
+    }
+}
+
+// an LED ring on a serial port
+pub struct SerialLights{
+    port: Box<dyn serialport::SerialPort>,
+    reader: BufReader<Box<dyn serialport::SerialPort>>,
+}
+
+impl SerialLights{
+    pub fn open(port_name: &str, baud_rate: u32)->Result<Self>{
+        let context = "open the light ring";
+        let port = serialport::new(port_name, baud_rate)
+            .timeout(SERIAL_TIMEOUT)
+            .open()
+            .map_err(|e|Error::capture(context, &format!("{}: {}", port_name, e)))?;
+        let reader = port.try_clone().map_err(|e|Error::capture(context, &format!("{}: {}", port_name, e)))?;
+        let mut lights = SerialLights{port, reader: BufReader::new(reader)};
+        let mut answer = Err(Error::capture(context, &format!("no light ring on {}", port_name)));
+        for _ in 0..PING_ATTEMPTS{
+            answer = lights.command("PING");
+            if answer.is_ok(){
+                break;
+            }
+        }
+        answer?;
+        // a late answer to an earlier ping must not be taken for the answer to the next command
+        std::thread::sleep(Duration::from_millis(50));
+        let _ = lights.port.clear(serialport::ClearBuffer::Input);
+        let SerialLights{port, reader} = lights;
+        Ok(SerialLights{port, reader: BufReader::new(reader.into_inner())})
+    }
+    fn command(&mut self, command: &str)->Result<()>{
+        let context = format!("send {} to the light ring", command);
+        self.port.write_all(format!("{}\n", command).as_bytes())
+            .and_then(|_|self.port.flush())
+            .map_err(|e|Error::capture(&context, &e.to_string()))?;
+        let mut answer = String::new();
+        self.reader.read_line(&mut answer).map_err(|e|Error::capture(&context, &format!("no answer: {}", e)))?;
+        let answer = answer.trim();
+        if answer == "OK"{
+            Ok(())
+        }
+        else if let Some(reason) = answer.strip_prefix("ERR"){
+            Err(Error::capture(&context, &format!("the light ring refused: {}", reason.trim())))
+        }
+        else{
+            Err(Error::capture(&context, &format!("unexpected answer {:?}", answer)))
+        }
+    }
+}
+
+impl LightController for SerialLights{
+    fn prepare(&mut self, shot: Shot)->Result<()>{
+        match shot{
+            Shot::Gray=>self.command("ALL"),
+            Shot::Take(index)=>self.command(&format!("DIR {}", index)),
+        }
+    }
+    fn finish(&mut self){
+        let _ = self.command("OFF");
+    }
+}
+
+// serial lights on a fake ring, the ring lives as long as the controller
+#[cfg(unix)]
+struct FakeRingLights{
+    lights: SerialLights,
+    _ring: super::fake_ring::FakeLightRing,
+}
+
+#[cfg(unix)]
+impl LightController for FakeRingLights{
+    fn prepare(&mut self, shot: Shot)->Result<()>{
+        self.lights.prepare(shot)
+    }
+    fn finish(&mut self){
+        self.lights.finish();
+    }
+}
+
+#[cfg(unix)]
+fn create_fake_lights<'a>()->Result<Box<dyn LightController + 'a>>{
+    let ring = super::fake_ring::FakeLightRing::start()?;
+    let lights = SerialLights::open(&ring.path().to_string_lossy(), LightSettings::default().get_baud_rate())?;
+    Ok(Box::new(FakeRingLights{lights, _ring: ring}))
+}
+
+#[cfg(not(unix))]
+fn create_fake_lights<'a>()->Result<Box<dyn LightController + 'a>>{
+    Err(Error::capture("start the fake light ring", &format!("{} needs pseudo terminals, which only exist on unix", FAKE_LIGHT_RING_ENV)))
+}
+
+// the controller of settings, prompt is used by the manual one
+pub fn create_controller<'a, F>(settings: &LightSettings, prompt: F)->Result<Box<dyn LightController + 'a>>
+    where F: FnMut(&str)->Result<()> + 'a
+{
+    if std::env::var_os(FAKE_LIGHT_RING_ENV).is_some(){
+        return create_fake_lights();
+    }
+    match settings.get_controller(){
+        LightControllerKind::None=>Ok(Box::new(NoLights)),
+        LightControllerKind::Manual=>Ok(Box::new(ManualLights::new(prompt))),
+        LightControllerKind::Serial=>Ok(Box::new(SerialLights::open(settings.get_port(), settings.get_baud_rate())?)),
+    }
+}
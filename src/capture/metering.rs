@@ -0,0 +1,136 @@
+// gray card auto-exposure. test frames of the evenly lit card are shot and the shutter speed
+// is moved until the card reads TARGET_LUMINANCE, the iso and aperture stay as chosen.
+use std::path::Path;
+use super::Shot;
+use super::light::LightController;
+use super::super::camera::SharedCamera;
+use super::super::exposure::{Exposure, ShutterSpeed};
+use super::super::error::{Error, Result};
+use super::super::processing::{self, Image};
+
+// linear luminance of an 18% gray card at a normal exposure
+pub const TARGET_LUMINANCE: f32 = 0.18;
+// close enough within half of a third stop, the finest step of the shutter speed
+const TOLERANCE_STOPS: f32 = 1.0 / 6.0;
+// the card lies where the sample will, its region is the middle of the frame. fraction of width and height
+const CARD_REGION: f32 = 0.5;
+// a channel at 250 of 255 in the jpeg is taken as clipped
+const CLIP_LEVEL: f32 = 250.0 / 255.0;
+// fraction of clipped pixels let through for hot pixels and specks on the card
+const CLIP_TOLERANCE: f32 = 0.001;
+// largest correction of one step in stops, a black or white frame tells little about the right exposure
+const MAX_STEP_STOPS: f32 = 4.0;
+const MAX_TEST_SHOTS: usize = 8;
+const TEST_FILE_NAME: &str = "exposure_test.jpg";
+
+// what a test frame shows of the card
+#[derive(Debug, Clone, Copy)]
+pub struct Metering{
+    // mean linear luminance of the card region
+    pub luminance: f32,
+    // fraction of the card region with a clipped channel
+    pub clipped: f32,
+}
+
+impl Metering{
+    // stops above the target, negative when the card is too dark
+    pub fn stops_from_target(&self)->f32{
+        if self.luminance > 0.0{
+            (self.luminance / TARGET_LUMINANCE).log2()
+        }
+        else{
+            -MAX_STEP_STOPS
+        }
+    }
+    pub fn is_clipped(&self)->bool{
+        self.clipped > CLIP_TOLERANCE
+    }
+    pub fn is_on_target(&self)->bool{
+        !self.is_clipped() && self.stops_from_target().abs() <= TOLERANCE_STOPS
+    }
+}
+
+// meter the card region of a linear image
+pub fn measure(img: &Image)->Result<Metering>{
+    let margin = (1.0 - CARD_REGION) / 2.0;
+    let (x0, y0) = ((img.width() as f32 * margin) as u32, (img.height() as f32 * margin) as u32);
+    let (x1, y1) = (img.width() - x0, img.height() - y0);
+    if x1 <= x0 || y1 <= y0{
+        return Err(Error::capture("meter the gray card", &format!("a {}x{} frame is too small to meter", img.width(), img.height())));
+    }
+    let clip = processing::srgb_to_linear(CLIP_LEVEL);
+    let (mut sum, mut clipped) = (0.0f64, 0usize);
+    for y in y0..y1{
+        for x in x0..x1{
+            let p = img.get(x, y);
+            sum += processing::luminance(p) as f64;
+            if p.iter().any(|c|*c >= clip){
+                clipped += 1;
+            }
+        }
+    }
+    let count = ((x1 - x0) * (y1 - y0)) as f64;
+    Ok(Metering{luminance: (sum / count) as f32, clipped: (clipped as f64 / count) as f32})
+}
+
+// find the shutter speed that puts the gray card of process_dir on target, starting from start.
+// after each test frame step is called with the exposure it was shot at, an error stops metering.
+pub fn auto_expose(camera: &SharedCamera, lights: &mut dyn LightController, process_dir: &Path, start: &Exposure, step: &mut dyn FnMut(&Exposure, &Metering)->Result<()>)->Result<Exposure>{
+    let context = "meter the gray card";
+    let shutter_speeds = {
+        let mut camera = camera.lock().unwrap();
+        if !camera.is_session_open(){
+            return Err(Error::camera(context, "no camera is connected"));
+        }
+        camera.set_iso(start.iso.speed())?;
+        camera.set_av(start.av.label())?;
+        // only the shutter speeds the exposure tables know, like the exposure controls
+        camera.supported_tv()?.iter()
+            .filter_map(|label|ShutterSpeed::parse(label).ok().filter(|tv|tv.label() == label))
+            .collect::<Vec<_>>()
+    };
+    if shutter_speeds.is_empty(){
+        return Err(Error::camera(context, "the camera offers no shutter speed"));
+    }
+    let test_path = process_dir.join(TEST_FILE_NAME);
+    let result = lights.prepare(Shot::Gray)
+        .and_then(|_|meter_frames(camera, &test_path, start, &shutter_speeds, step));
+    lights.finish();
+    let _ = std::fs::remove_file(&test_path);
+    result
+}
+
+fn meter_frames(camera: &SharedCamera, test_path: &Path, start: &Exposure, shutter_speeds: &[ShutterSpeed], step: &mut dyn FnMut(&Exposure, &Metering)->Result<()>)->Result<Exposure>{
+    let context = "meter the gray card";
+    let mut exposure = Exposure{tv: nearest(shutter_speeds, start.tv.tv()), ..*start};
+    for _ in 0..MAX_TEST_SHOTS{
+        {
+            let mut camera = camera.lock().unwrap();
+            camera.set_tv(exposure.tv.label())?;
+            camera.capture(test_path)?;
+        }
+        let metering = measure(&Image::load_linear(test_path)?)?;
+        step(&exposure, &metering)?;
+        if metering.is_on_target(){
+            return Ok(exposure);
+        }
+        let mut correction = (-metering.stops_from_target()).clamp(-MAX_STEP_STOPS, MAX_STEP_STOPS);
+        if metering.is_clipped(){
+            // clipping hides how bright the card is, at least a stop down
+            correction = correction.min(-1.0);
+        }
+        // a longer time is a smaller tv
+        let tv = nearest(shutter_speeds, exposure.tv.tv() - correction);
+        if tv == exposure.tv{
+            return Err(Error::capture(context, &format!("the gray card reads {:.3} at {}, the camera has no shutter speed to reach {}", metering.luminance, exposure, TARGET_LUMINANCE)));
+        }
+        exposure.tv = tv;
+    }
+    Err(Error::capture(context, &format!("the exposure did not settle within {} test shots", MAX_TEST_SHOTS)))
+}
+
+fn nearest(shutter_speeds: &[ShutterSpeed], tv: f32)->ShutterSpeed{
+    *shutter_speeds.iter()
+        .min_by(|a, b|(a.tv() - tv).abs().partial_cmp(&(b.tv() - tv).abs()).unwrap())
+        .unwrap()
+}
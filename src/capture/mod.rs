@@ -0,0 +1,215 @@
+// shoots the frames of a process: the gray frame, then the directional takes in order
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use super::camera::SharedCamera;
+use super::config::{self, ProcessingState};
+use super::config::manifest::ProcessStatus;
+use super::error::{Error, Result};
+
+pub mod light;
+use light::LightController;
+pub mod metering;
+#[cfg(unix)]
+pub mod fake_ring;
+
+// one frame of a process
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Shot{
+    // evenly lit gray card, flat-fields the albedo
+    Gray,
+    // lit from light direction index
+    Take(usize),
+}
+
+impl Shot{
+    pub fn file_name(&self)->String{
+        match self{
+            Shot::Gray=>config::GRAY_FILE_NAME.to_string(),
+            Shot::Take(index)=>config::take_file_name(*index),
+        }
+    }
+    // the whole sequence
+    pub fn all()->Vec<Shot>{
+        let mut shots = vec![Shot::Gray];
+        shots.extend((0..config::TAKE_COUNT).map(Shot::Take));
+        shots
+    }
+    // the frames state does not have yet, in sequence order
+    pub fn missing(state: &ProcessingState)->Vec<Shot>{
+        Self::all().into_iter().filter(|shot|!shot.is_taken(state)).collect()
+    }
+    fn is_taken(&self, state: &ProcessingState)->bool{
+        match self{
+            Shot::Gray=>state.has_gray(),
+            Shot::Take(index)=>state.has_take(*index),
+        }
+    }
+    fn mark_taken(&self, state: &mut ProcessingState){
+        match self{
+            Shot::Gray=>state.set_gray(true),
+            Shot::Take(index)=>state.set_take(*index, true),
+        }
+    }
+}
+
+// state.json of a process, empty when nothing was shot yet
+pub fn load_state(process_dir: &Path)->ProcessingState{
+    let mut state = ProcessingState::new();
+    let path = process_dir.join(config::PROCESS_STATE_FILE_NAME);
+    if path.exists(){
+        let _ = state.load(&path);
+    }
+    state
+}
+
+// where a process with state stands in the project manifest
+pub fn capture_status(state: &ProcessingState)->ProcessStatus{
+    let missing = Shot::missing(state).len();
+    if missing == 0{
+        ProcessStatus::Captured
+    }
+    else if missing < Shot::all().len(){
+        ProcessStatus::Capturing
+    }
+    else{
+        ProcessStatus::New
+    }
+}
+
+// shoot shots in order into process_dir. state.json is saved after every frame,
+// so an interrupted sequence can be finished with Shot::missing.
+// before_shot is called with the shot and its position in shots, an error stops the sequence.
+// lights are set up for each shot right before it is taken.
+// focal_length is recorded in state.json for the undistortion, 0 when it is not known.
+pub fn shoot(camera: &SharedCamera, lights: &mut dyn LightController, process_dir: &Path, shots: &[Shot], focal_length: f32, before_shot: &mut dyn FnMut(Shot, usize)->Result<()>)->Result<ProcessingState>{
+    let context = "shoot the process";
+    if let Some(Shot::Take(index)) = shots.iter().find(|shot|matches!(shot, Shot::Take(index) if *index >= config::TAKE_COUNT)){
+        return Err(Error::validation(context, &format!("there is no take {}, a process has {} takes", index, config::TAKE_COUNT)));
+    }
+    if !process_dir.join(config::PROCESS_SETTING_FILE_NAME).exists(){
+        return Err(Error::validation(context, &format!("{} is not a process", process_dir.display())));
+    }
+    if !camera.lock().unwrap().is_session_open(){
+        return Err(Error::camera(context, "no camera is connected"));
+    }
+    // the frames of a process share one lens model, the ones that are kept have to match
+    let state = load_state(process_dir);
+    let recorded = state.get_lens().get_focal_length();
+    let keeps_frames = Shot::all().iter().any(|shot|shot.is_taken(&state) && !shots.contains(shot));
+    if focal_length > 0.0 && recorded > 0.0 && (focal_length - recorded).abs() > 1e-3 && keeps_frames{
+        let message = format!("the frames so far were shot at {}mm, shoot all of them again at {}mm", recorded, focal_length);
+        return Err(Error::validation(context, &message));
+    }
+    let result = shoot_frames(camera, lights, process_dir, shots, focal_length, before_shot);
+    lights.finish();
+    result
+}
+
+fn shoot_frames(camera: &SharedCamera, lights: &mut dyn LightController, process_dir: &Path, shots: &[Shot], focal_length: f32, before_shot: &mut dyn FnMut(Shot, usize)->Result<()>)->Result<ProcessingState>{
+    let state_path = process_dir.join(config::PROCESS_STATE_FILE_NAME);
+    let mut state = load_state(process_dir);
+    if focal_length > 0.0{
+        state.set_focal_length(focal_length);
+    }
+    for (i, shot) in shots.iter().enumerate(){
+        before_shot(*shot, i)?;
+        lights.prepare(*shot)?;
+        // lock per frame so exposure changes from the ui are not blocked for the whole sequence
+        camera.lock().unwrap().capture(&process_dir.join(shot.file_name()))?;
+        shot.mark_taken(&mut state);
+        state.save(&state_path)?;
+    }
+    Ok(state)
+}
+
+#[cfg(all(test, unix))]
+mod tests{
+    use std::sync::{Arc, Mutex};
+    use super::*;
+    use super::fake_ring::FakeLightRing;
+    use super::light::{NoLights, SerialLights};
+    use super::super::camera::{CameraBackend, SimulatedBackend};
+    use super::super::config::{LightSettings, ProcessingSettings};
+    use super::super::testing::TempDir;
+
+    // a process directory and a simulated camera with one frame to serve
+    fn setup(dir: &TempDir)->(SharedCamera, std::path::PathBuf){
+        let frame_dir = dir.path().join("frames");
+        std::fs::create_dir_all(&frame_dir).unwrap();
+        image::RgbImage::new(4, 4).save(frame_dir.join("frame.png")).unwrap();
+        let mut backend = SimulatedBackend::new(&frame_dir);
+        backend.open_session(0).unwrap();
+        let process_dir = dir.path().join("process");
+        std::fs::create_dir_all(&process_dir).unwrap();
+        ProcessingSettings::new().save(&process_dir.join(config::PROCESS_SETTING_FILE_NAME)).unwrap();
+        (Arc::new(Mutex::new(Box::new(backend) as Box<dyn CameraBackend>)), process_dir)
+    }
+
+    fn open_lights(ring: &FakeLightRing)->SerialLights{
+        SerialLights::open(&ring.path().to_string_lossy(), LightSettings::default().get_baud_rate()).unwrap()
+    }
+
+    #[test]
+    fn shoot_switches_the_ring_before_every_frame(){
+        let dir = TempDir::new("shoot");
+        let (camera, process_dir) = setup(&dir);
+        let ring = FakeLightRing::start().unwrap();
+        let mut lights = open_lights(&ring);
+        let state = shoot(&camera, &mut lights, &process_dir, &Shot::all(), 0.0, &mut |_, _|Ok(())).unwrap();
+        let mut expected = vec!["PING".to_string(), "ALL".to_string()];
+        expected.extend((0..config::TAKE_COUNT).map(|index|format!("DIR {}", index)));
+        expected.push("OFF".to_string());
+        assert_eq!(ring.commands(), expected);
+        assert!(Shot::missing(&state).is_empty());
+        assert!(Shot::missing(&load_state(&process_dir)).is_empty());
+        for shot in Shot::all(){
+            assert!(process_dir.join(shot.file_name()).exists(), "{} was not shot", shot.file_name());
+        }
+    }
+
+    #[test]
+    fn shoot_stops_when_the_sequence_is_stopped_and_turns_the_ring_off(){
+        let dir = TempDir::new("shoot_stopped");
+        let (camera, process_dir) = setup(&dir);
+        let ring = FakeLightRing::start().unwrap();
+        let mut lights = open_lights(&ring);
+        let result = shoot(&camera, &mut lights, &process_dir, &Shot::all(), 0.0, &mut |_, i|{
+            if i == 2 { Err(Error::Cancelled) } else { Ok(()) }
+        });
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert_eq!(ring.commands(), vec!["PING", "ALL", "DIR 0", "OFF"]);
+        assert_eq!(Shot::missing(&load_state(&process_dir)).len(), config::TAKE_COUNT - 1);
+    }
+
+    #[test]
+    fn shoot_records_the_focal_length(){
+        let dir = TempDir::new("shoot_focal_length");
+        let (camera, process_dir) = setup(&dir);
+        let mut lights = NoLights;
+        shoot(&camera, &mut lights, &process_dir, &[Shot::Gray, Shot::Take(0)], 35.0, &mut |_, _|Ok(())).unwrap();
+        assert_eq!(load_state(&process_dir).get_lens().get_focal_length(), 35.0);
+        // an unknown focal length keeps the recorded one
+        shoot(&camera, &mut lights, &process_dir, &[Shot::Take(1)], 0.0, &mut |_, _|Ok(())).unwrap();
+        assert_eq!(load_state(&process_dir).get_lens().get_focal_length(), 35.0);
+        // another one would mix two lens models in the kept frames
+        let result = shoot(&camera, &mut lights, &process_dir, &[Shot::Take(2)], 50.0, &mut |_, _|Ok(()));
+        assert!(matches!(result, Err(Error::Validation{..})));
+        assert!(!load_state(&process_dir).has_take(2));
+        // unless every kept frame is shot again
+        let state = shoot(&camera, &mut lights, &process_dir, &Shot::all(), 50.0, &mut |_, _|Ok(())).unwrap();
+        assert_eq!(state.get_lens().get_focal_length(), 50.0);
+    }
+
+    #[test]
+    fn a_refused_command_is_a_capture_error(){
+        let ring = FakeLightRing::start().unwrap();
+        let mut lights = open_lights(&ring);
+        let error = lights.prepare(Shot::Take(config::TAKE_COUNT)).unwrap_err();
+        assert!(matches!(error, Error::Capture{..}), "{}", error);
+        assert_eq!(error.message(), format!("the light ring refused: unknown command DIR {}", config::TAKE_COUNT));
+        // the ring keeps answering after a refusal
+        lights.prepare(Shot::Gray).unwrap();
+        assert_eq!(ring.commands(), vec!["PING".to_string(), format!("DIR {}", config::TAKE_COUNT), "ALL".to_string()]);
+    }
+}
@@ -0,0 +1,217 @@
+// headless mode for render nodes and ci, no web-view
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use super::camera;
+use super::capture::{self, Shot};
+use super::capture::light::{self, LightController};
+use super::config::{LightControllerKind, LightSettings, ProjectSettings};
+use super::config::manifest::{self, ProcessStatus};
+use super::error::{Error, Result};
+use super::processing;
+
+const USAGE: &str = "usage:
+    tex_shooter                                  start the gui
+    tex_shooter process <root> <process_name>    build the texture maps of a process
+    tex_shooter process <root> --all             build the texture maps of every process in root
+    tex_shooter capture <root> <process_name> [--missing] [--lights none|manual|fake|<serial port>] [--focal-length <mm>]
+                                                 shoot the gray frame and the takes of a process,
+                                                 fake is an LED ring on a pseudo terminal (unix only),
+                                                 the focal length is recorded for the undistortion";
+
+// exit codes
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+// true when the arguments ask for the headless mode
+pub fn is_requested(args: &[String])->bool{
+    args.len() > 1
+}
+
+// run the command in args (args[0] is the executable) and return the exit code
+pub fn run(args: &[String])->i32{
+    let args: Vec<&str> = args.iter().skip(1).map(|arg|arg.as_str()).collect();
+    match args.as_slice(){
+        ["process", root, "--all"]=>process_all(root),
+        ["process", root, process_name] if !process_name.starts_with("--")=>process_one(root, process_name),
+        ["capture", root, process_name, options @ ..] if !process_name.starts_with("--")=>{
+            match parse_capture_options(options){
+                Some((missing, lights, focal_length))=>capture_one(root, process_name, missing, &lights, focal_length),
+                None=>{
+                    eprintln!("{}", USAGE);
+                    EXIT_USAGE
+                }
+            }
+        }
+        ["help"] | ["--help"] | ["-h"]=>{
+            println!("{}", USAGE);
+            EXIT_SUCCESS
+        }
+        _=>{
+            eprintln!("{}", USAGE);
+            EXIT_USAGE
+        }
+    }
+}
+
+fn process_one(root: &str, process_name: &str)->i32{
+    let project = ProjectSettings::with_root(root);
+    match run_process(&project, process_name){
+        Ok(())=>EXIT_SUCCESS,
+        Err(error)=>{
+            eprintln!("{}: {}", process_name, error);
+            EXIT_FAILURE
+        }
+    }
+}
+
+// keep going after a failed process, but report the failure in the exit code
+fn process_all(root: &str)->i32{
+    let project = ProjectSettings::with_root(root);
+    let mut process_list = match project.calc_process_list(){
+        Ok(process_list)=>process_list,
+        Err(error)=>{
+            eprintln!("{}", error);
+            return EXIT_FAILURE;
+        }
+    };
+    if process_list.is_empty(){
+        eprintln!("no process in {}", root);
+        return EXIT_FAILURE;
+    }
+    process_list.sort();
+    let mut failed = 0;
+    for path in &process_list{
+        let process_name = Path::new(path).file_name().map(|n|n.to_string_lossy().into_owned()).unwrap_or_default();
+        if let Err(error) = run_process(&project, &process_name){
+            eprintln!("{}: {}", process_name, error);
+            failed += 1;
+        }
+    }
+    println!("{} of {} processes done", process_list.len() - failed, process_list.len());
+    if failed > 0 { EXIT_FAILURE } else { EXIT_SUCCESS }
+}
+
+fn run_process(project: &ProjectSettings, process_name: &str)->Result<()>{
+    let process_dir = project.get_process_path(process_name);
+    let mut settings = project.load_process(process_name)?;
+    let report = processing::process(&process_dir, &mut settings, &mut |stage, _|{
+        println!("{}: {}", process_name, stage);
+        Ok(())
+    })?;
+    if let Some(trim) = report.trim{
+        if trim.confidence < processing::boundary::MIN_CONFIDENCE{
+            eprintln!("{}: the sample was found with low confidence ({:.2}), check the trim points", process_name, trim.confidence);
+        }
+    }
+    for warning in &report.warnings{
+        eprintln!("{}: {}", process_name, warning);
+    }
+    for stage in &report.reused{
+        println!("{}: reused the cached {}", process_name, stage.name());
+    }
+    for output in &report.outputs{
+        println!("{}: wrote {}", process_name, output.display());
+    }
+    manifest::update_process_status(Path::new(project.get_root_path()), process_name, ProcessStatus::Processed)?;
+    Ok(())
+}
+
+// --missing, the --lights value and the --focal-length value, 0 when it is not given
+fn parse_capture_options(options: &[&str])->Option<(bool, String, f32)>{
+    let mut missing = false;
+    let mut lights = "none".to_string();
+    let mut focal_length = 0.0;
+    let mut i = 0;
+    while i < options.len(){
+        match options[i]{
+            "--missing"=>missing = true,
+            "--lights" if i + 1 < options.len()=>{
+                i += 1;
+                lights = options[i].to_string();
+            }
+            "--focal-length" if i + 1 < options.len()=>{
+                i += 1;
+                focal_length = options[i].parse().ok().filter(|mm: &f32|*mm > 0.0)?;
+            }
+            _=>return None,
+        }
+        i += 1;
+    }
+    Some((missing, lights, focal_length))
+}
+
+fn capture_one(root: &str, process_name: &str, missing: bool, lights: &str, focal_length: f32)->i32{
+    let project = ProjectSettings::with_root(root);
+    match run_capture(&project, process_name, missing, lights, focal_length){
+        Ok(())=>EXIT_SUCCESS,
+        Err(error)=>{
+            eprintln!("{}: {}", process_name, error);
+            EXIT_FAILURE
+        }
+    }
+}
+
+// the operator answers on stdin
+fn prompt_operator(message: &str)->Result<()>{
+    println!("{}, then press enter", message);
+    let mut line = String::new();
+    if std::io::stdin().lock().read_line(&mut line).map_err(|e|Error::io(Path::new("stdin"), "read the answer of the operator", e))? == 0{
+        return Err(Error::capture("prepare the lights", "the operator did not answer"));
+    }
+    Ok(())
+}
+
+fn run_capture(project: &ProjectSettings, process_name: &str, missing: bool, lights: &str, focal_length: f32)->Result<()>{
+    let process_dir = project.get_process_path(process_name);
+    let shots = if missing { Shot::missing(&capture::load_state(&process_dir)) } else { Shot::all() };
+    let mut backend = camera::create_backend()?;
+    if backend.list_devices().is_empty(){
+        return Err(Error::camera("open the camera session", "no camera is connected"));
+    }
+    println!("{}: camera {}", process_name, backend.open_session(0)?);
+    // the exposure the process was shot with, so a re-shoot matches the earlier frames
+    let settings = project.load_process(process_name)?;
+    let exposure = settings.get_exposure().to_exposure()?;
+    backend.set_iso(exposure.iso.speed())?;
+    backend.set_av(exposure.av.label())?;
+    backend.set_tv(exposure.tv.label())?;
+    // what the body holds now, not what was asked for
+    println!("{}: exposure ISO {} f/{} {}", process_name, backend.get_iso()?, backend.get_av()?, backend.get_tv()?);
+    let camera: camera::SharedCamera = Arc::new(Mutex::new(backend));
+    let mut before_shot = |shot: Shot, _|{
+        println!("{}: shooting {}", process_name, shot.file_name());
+        Ok(())
+    };
+    let result = match lights{
+        "fake"=>run_capture_on_fake_ring(&camera, &process_dir, &shots, focal_length, &mut before_shot),
+        _=>{
+            let settings = match lights{
+                "none"=>LightSettings::new(LightControllerKind::None, "", LightSettings::default().get_baud_rate()),
+                "manual"=>LightSettings::new(LightControllerKind::Manual, "", LightSettings::default().get_baud_rate()),
+                port=>LightSettings::new(LightControllerKind::Serial, port, LightSettings::default().get_baud_rate()),
+            };
+            let mut controller = light::create_controller(&settings, prompt_operator)?;
+            capture::shoot(&camera, controller.as_mut(), &process_dir, &shots, focal_length, &mut before_shot).map(|_|())
+        }
+    };
+    // also after a stop half way, the frames shot so far count
+    let status = capture::capture_status(&capture::load_state(&process_dir));
+    manifest::update_process_status(Path::new(project.get_root_path()), process_name, status)?;
+    result
+}
+
+#[cfg(unix)]
+fn run_capture_on_fake_ring(camera: &camera::SharedCamera, process_dir: &Path, shots: &[Shot], focal_length: f32, before_shot: &mut dyn FnMut(Shot, usize)->Result<()>)->Result<()>{
+    let ring = capture::fake_ring::FakeLightRing::start()?;
+    let mut controller = light::SerialLights::open(&ring.path().to_string_lossy(), LightSettings::default().get_baud_rate())?;
+    let result = capture::shoot(camera, &mut controller as &mut dyn LightController, process_dir, shots, focal_length, before_shot);
+    println!("light ring on {} received: {}", ring.path().display(), ring.commands().join(", "));
+    result.map(|_|())
+}
+
+#[cfg(not(unix))]
+fn run_capture_on_fake_ring(_camera: &camera::SharedCamera, _process_dir: &Path, _shots: &[Shot], _focal_length: f32, _before_shot: &mut dyn FnMut(Shot, usize)->Result<()>)->Result<()>{
+    Err(Error::capture("start the fake light ring", "the fake light ring needs pseudo terminals, which only exist on unix"))
+}
@@ -0,0 +1,229 @@
+// renaming, duplicating, archiving and deleting processes of a project.
+// every name from the ui goes through validate_process_name first, so nothing is touched outside the root.
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use chrono::Utc;
+use zip::write::FileOptions;
+use zip::CompressionMethod;
+use super::{ProjectSettings, GRAY_FILE_NAME, PROCESS_CACHE_DIR_NAME, PROCESS_SETTING_FILE_NAME, PROCESS_STATE_FILE_NAME, TAKE_COUNT, take_file_name};
+use super::manifest::ProcessStatus;
+use super::super::error::{Error, Result};
+
+// directory under the root with the archived processes
+pub const ARCHIVE_DIR_NAME: &str = "archive";
+
+// characters windows does not allow in a file name, the separators among them
+const FORBIDDEN_CHARACTERS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+// device names windows reserves, also with an extension such as nul.txt
+const RESERVED_NAMES: &[&str] = &["CON", "PRN", "AUX", "NUL"];
+const RESERVED_NUMBERED_NAMES: &[&str] = &["COM", "LPT"];
+
+// a process name is a single plain directory name right under the root
+pub fn validate_process_name(name: &str)->Result<()>{
+    let invalid = |reason: &str|Err(Error::validation("use the process name", &format!("\"{}\" {}", name, reason)));
+    if name.trim().is_empty(){
+        return invalid("is empty");
+    }
+    if name.trim() != name{
+        return invalid("starts or ends with a space");
+    }
+    if name.chars().any(|c|FORBIDDEN_CHARACTERS.contains(&c) || c.is_control()){
+        return invalid("contains a path separator or a character a file name cannot have");
+    }
+    if name.starts_with('.'){
+        return invalid("starts with a dot");
+    }
+    // windows drops a trailing dot, the directory would get another name than the process
+    if name.ends_with('.'){
+        return invalid("ends with a dot");
+    }
+    if is_reserved_name(name){
+        return invalid("is a device name windows reserves");
+    }
+    if name == ARCHIVE_DIR_NAME{
+        return invalid("is where archived processes are kept");
+    }
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()){
+        (Some(Component::Normal(_)), None)=>Ok(()),
+        _=>invalid("is not a plain directory name"),
+    }
+}
+
+fn is_reserved_name(name: &str)->bool{
+    let stem = name.split('.').next().unwrap_or(name).trim_end().to_ascii_uppercase();
+    if RESERVED_NAMES.contains(&stem.as_str()){
+        return true;
+    }
+    RESERVED_NUMBERED_NAMES.iter().any(|prefix|{
+        stem.len() == prefix.len() + 1 && stem.starts_with(prefix) && matches!(stem.as_bytes()[prefix.len()], b'1'..=b'9')
+    })
+}
+
+impl ProjectSettings{
+    pub fn rename_process(&mut self, process_name: &str, new_name: &str)->Result<()>{
+        self.check_existing(process_name, "rename the process")?;
+        let to = self.check_free(new_name, "rename the process")?;
+        let from = self.get_process_path(process_name);
+        self.update_manifest(|manifest|{
+            fs::rename(&from, &to).map_err(|e|Error::io(&from, "rename the process", e))?;
+            manifest.rename_process(process_name, new_name)
+        })?;
+        if self.last_processing == process_name{
+            self.last_processing = new_name.to_string();
+        }
+        Ok(())
+    }
+    // a new process with the settings of process_name, with_captures also copies the shot frames
+    pub fn duplicate_process(&self, process_name: &str, new_name: &str, with_captures: bool)->Result<()>{
+        self.check_existing(process_name, "duplicate the process")?;
+        let to = self.check_free(new_name, "duplicate the process")?;
+        let from = self.get_process_path(process_name);
+        self.update_manifest(|manifest|{
+            fs::create_dir(&to).map_err(|e|Error::io(&to, "duplicate the process", e))?;
+            let mut files = vec![PROCESS_SETTING_FILE_NAME.to_string()];
+            if with_captures{
+                files.push(PROCESS_STATE_FILE_NAME.to_string());
+                files.push(GRAY_FILE_NAME.to_string());
+                files.extend((0..TAKE_COUNT).map(take_file_name));
+            }
+            let copied = files.iter()
+                .filter(|file_name|from.join(file_name).exists())
+                .try_for_each(|file_name|{
+                    fs::copy(from.join(file_name), to.join(file_name)).map(|_|()).map_err(|e|Error::io(&from.join(file_name), "duplicate the process", e))
+                });
+            if let Err(error) = copied{
+                // the half made copy is ours, nothing of the operator is lost
+                let _ = fs::remove_dir_all(&to);
+                return Err(error);
+            }
+            // the texture maps are not copied, a processed source leaves a captured copy
+            let status = match manifest.get_process(process_name).map(|entry|entry.get_status()){
+                Some(ProcessStatus::Processed) | Some(ProcessStatus::Captured) if with_captures=>ProcessStatus::Captured,
+                Some(ProcessStatus::Capturing) if with_captures=>ProcessStatus::Capturing,
+                _=>ProcessStatus::New,
+            };
+            manifest.add_process(new_name);
+            manifest.set_status(new_name, status)
+        })
+    }
+    // pack the process into a zip under the archive directory and move it to the trash, the zip path is returned
+    pub fn archive_process(&mut self, process_name: &str)->Result<PathBuf>{
+        self.check_existing(process_name, "archive the process")?;
+        let archive_dir = Path::new(&self.root_path).join(ARCHIVE_DIR_NAME);
+        let process_dir = self.get_process_path(process_name);
+        let zip_path = self.update_manifest(|manifest|{
+            fs::create_dir_all(&archive_dir).map_err(|e|Error::io(&archive_dir, "archive the process", e))?;
+            let mut zip_path = archive_dir.join(format!("{}.zip", process_name));
+            if zip_path.exists(){
+                zip_path = archive_dir.join(format!("{}-{}.zip", process_name, Utc::now().format("%Y%m%d-%H%M%S")));
+            }
+            // written under another name first, a zip that is there is complete
+            let part_path = zip_path.with_extension("zip.part");
+            if let Err(error) = write_zip(&process_dir, process_name, &part_path){
+                let _ = fs::remove_file(&part_path);
+                return Err(error);
+            }
+            fs::rename(&part_path, &zip_path).map_err(|e|Error::io(&zip_path, "archive the process", e))?;
+            trash_dir(&process_dir, "archive the process")?;
+            manifest.remove_process(process_name);
+            Ok(zip_path)
+        })?;
+        self.forget_selection(process_name);
+        Ok(zip_path)
+    }
+    // move the process to the trash of the system, it can be restored from there
+    pub fn delete_process(&mut self, process_name: &str)->Result<()>{
+        self.check_existing(process_name, "delete the process")?;
+        let process_dir = self.get_process_path(process_name);
+        self.update_manifest(|manifest|{
+            trash_dir(&process_dir, "delete the process")?;
+            manifest.remove_process(process_name);
+            Ok(())
+        })?;
+        self.forget_selection(process_name);
+        Ok(())
+    }
+    fn check_existing(&self, process_name: &str, context: &str)->Result<()>{
+        validate_process_name(process_name)?;
+        if !self.exist_process(process_name){
+            return Err(Error::validation(context, &format!("{} is not a process in {}", process_name, self.root_path)));
+        }
+        Ok(())
+    }
+    // the path for a new process, which must not be there yet
+    fn check_free(&self, new_name: &str, context: &str)->Result<PathBuf>{
+        validate_process_name(new_name)?;
+        let path = self.get_process_path(new_name);
+        if path.exists(){
+            return Err(Error::validation(context, &format!("{} already exists in {}", new_name, self.root_path)));
+        }
+        Ok(path)
+    }
+    fn forget_selection(&mut self, process_name: &str){
+        if self.last_processing == process_name{
+            self.last_processing.clear();
+        }
+    }
+}
+
+// every file under process_dir into a zip at zip_path, inside a directory named process_name
+fn write_zip(process_dir: &Path, process_name: &str, zip_path: &Path)->Result<()>{
+    let zip_error = |e: zip::result::ZipError|Error::io(zip_path, "archive the process", io::Error::other(e.to_string()));
+    let file = File::create(zip_path).map_err(|e|Error::io(zip_path, "archive the process", e))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let mut dirs = vec![(process_dir.to_path_buf(), process_name.to_string())];
+    while let Some((dir, name_in_zip)) = dirs.pop(){
+        let mut entries = fs::read_dir(&dir).map_err(|e|Error::io(&dir, "archive the process", e))?
+            .map(|entry|entry.map(|entry|entry.path()))
+            .collect::<io::Result<Vec<_>>>()
+            .map_err(|e|Error::io(&dir, "archive the process", e))?;
+        entries.sort();
+        for path in entries{
+            let name = format!("{}/{}", name_in_zip, path.file_name().unwrap_or_default().to_string_lossy());
+            if path.is_dir(){
+                // the processing cache is rebuilt from the frames, it only makes the archive large
+                if dir != process_dir || !path.ends_with(PROCESS_CACHE_DIR_NAME){
+                    dirs.push((path, name));
+                }
+                continue;
+            }
+            // the frames are jpegs already, deflating them only costs time
+            let is_jpeg = path.extension().is_some_and(|ext|ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg"));
+            let method = if is_jpeg { CompressionMethod::Stored } else { CompressionMethod::Deflated };
+            zip.start_file(name, FileOptions::default().compression_method(method)).map_err(zip_error)?;
+            let data = fs::read(&path).map_err(|e|Error::io(&path, "archive the process", e))?;
+            zip.write_all(&data).map_err(|e|Error::io(zip_path, "archive the process", e))?;
+        }
+    }
+    zip.finish().map_err(zip_error)?;
+    Ok(())
+}
+
+fn trash_dir(dir: &Path, context: &str)->Result<()>{
+    trash::delete(dir).map_err(|e|Error::io(dir, context, io::Error::other(e.to_string())))
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn validate_process_name_accepts_plain_names(){
+        for name in ["sample_01", "oak plank", "brick-2", "v1.2", "Console", "com0", "lpt10"].iter(){
+            assert!(validate_process_name(name).is_ok(), "{} was rejected", name);
+        }
+    }
+
+    #[test]
+    fn validate_process_name_rejects_what_is_no_directory_of_its_own(){
+        let names = [
+            "", "  ", " sample", "sample ", "a/b", "a\\b", "a:b", "a*b", "a\u{7}b", ".hidden", "..", "name.",
+            ARCHIVE_DIR_NAME, "CON", "con.txt", "nul", "com1", "LPT9.png",
+        ];
+        for name in names.iter(){
+            assert!(matches!(validate_process_name(name), Err(Error::Validation{..})), "{:?} was accepted", name);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+// project.json at the root of a project: what the project is and which processes it has.
+// everything a project needs travels with its folder, so it can be moved, shared or opened elsewhere.
+use std::path::Path;
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use super::{load_versioned_json, save_to_json, PROCESS_SETTING_FILE_NAME};
+use super::migration::Document;
+use super::super::error::{Error, Result};
+
+pub const PROJECT_MANIFEST_FILE_NAME: &str = "project.json";
+
+// held while project.json is read for a change and written, the ui thread and jobs update it alike
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+// how far a process has come
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessStatus{
+    // nothing shot yet
+    New,
+    // some frames are shot
+    Capturing,
+    // the gray frame and every take are shot
+    Captured,
+    // the texture maps are built
+    Processed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProcessEntry{
+    // directory name of the process under the root
+    name: String,
+    status: ProcessStatus,
+    created: DateTime<Utc>,
+}
+impl ProcessEntry{
+    pub fn get_name(&self)->&str{
+        &self.name
+    }
+    pub fn get_status(&self)->ProcessStatus{
+        self.status
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProjectManifest{
+    schema_version: u32,
+    name: String,
+    uuid: Uuid,
+    created: DateTime<Utc>,
+    modified: DateTime<Utc>,
+    processes: Vec<ProcessEntry>,
+}
+
+impl ProjectManifest{
+    // an empty index for root, named after its directory. it gets a uuid when it is first written
+    fn empty(root: &Path)->Self{
+        let now = Utc::now();
+        ProjectManifest{
+            schema_version: Document::Manifest.current_version(),
+            name: root.file_name().map(|name|name.to_string_lossy().into_owned()).unwrap_or_default(),
+            uuid: Uuid::nil(),
+            created: now,
+            modified: now,
+            processes: Vec::new(),
+        }
+    }
+    // project.json of root as it is, with the index brought in line with the process directories on disk,
+    // e.g. after a copy by hand. nothing is written, None when the project has no project.json yet
+    pub fn read(root: &Path)->Result<Option<Self>>{
+        let path = root.join(PROJECT_MANIFEST_FILE_NAME);
+        if !path.exists(){
+            return Ok(None);
+        }
+        let mut manifest = ProjectManifest::empty(root);
+        load_versioned_json(&mut manifest, &path, Document::Manifest)?;
+        manifest.reconcile(root)?;
+        Ok(Some(manifest))
+    }
+    // the process directories of root, from project.json when there is one
+    pub fn read_processes(root: &Path)->Result<Vec<ProcessEntry>>{
+        match ProjectManifest::read(root)?{
+            Some(manifest)=>Ok(manifest.processes),
+            None=>{
+                let mut manifest = ProjectManifest::empty(root);
+                manifest.reconcile(root)?;
+                Ok(manifest.processes)
+            }
+        }
+    }
+    // change project.json of root and write it, it is created when the project has none.
+    // no other update reads the file in between, so changes from the ui and from jobs are not lost
+    pub fn update<T, F>(root: &Path, change: F)->Result<T>
+        where F: FnOnce(&mut ProjectManifest)->Result<T>
+    {
+        let _lock = WRITE_LOCK.lock().unwrap_or_else(|poisoned|poisoned.into_inner());
+        let mut manifest = match ProjectManifest::read(root)?{
+            Some(manifest)=>manifest,
+            None=>{
+                let mut manifest = ProjectManifest::empty(root);
+                manifest.uuid = Uuid::new_v4();
+                manifest.reconcile(root)?;
+                manifest
+            }
+        };
+        let value = change(&mut manifest)?;
+        manifest.modified = Utc::now();
+        save_to_json(&manifest, &root.join(PROJECT_MANIFEST_FILE_NAME))?;
+        Ok(value)
+    }
+    pub fn get_name(&self)->&str{
+        &self.name
+    }
+    pub fn get_uuid(&self)->&Uuid{
+        &self.uuid
+    }
+    pub fn get_created(&self)->&DateTime<Utc>{
+        &self.created
+    }
+    pub fn get_modified(&self)->&DateTime<Utc>{
+        &self.modified
+    }
+    pub fn get_process(&self, name: &str)->Option<&ProcessEntry>{
+        self.processes.iter().find(|entry|entry.name == name)
+    }
+    // a new process in the index, an existing one is left as it is
+    pub fn add_process(&mut self, name: &str){
+        if self.get_process(name).is_none(){
+            self.processes.push(ProcessEntry{name: name.to_string(), status: ProcessStatus::New, created: Utc::now()});
+        }
+    }
+    pub fn remove_process(&mut self, name: &str){
+        self.processes.retain(|entry|entry.name != name);
+    }
+    // the entry keeps its status and creation time under the new name
+    pub fn rename_process(&mut self, name: &str, new_name: &str)->Result<()>{
+        let entry = self.processes.iter_mut().find(|entry|entry.name == name)
+            .ok_or_else(||Error::validation("update the project", &format!("{} is not in the project", name)))?;
+        entry.name = new_name.to_string();
+        Ok(())
+    }
+    pub fn set_status(&mut self, name: &str, status: ProcessStatus)->Result<()>{
+        let entry = self.processes.iter_mut().find(|entry|entry.name == name)
+            .ok_or_else(||Error::validation("update the project", &format!("{} is not in the project", name)))?;
+        entry.status = status;
+        Ok(())
+    }
+    // add the process directories missing from the index and drop the entries without one
+    fn reconcile(&mut self, root: &Path)->Result<()>{
+        self.processes.retain(|entry|root.join(&entry.name).join(PROCESS_SETTING_FILE_NAME).exists());
+        let dir = match root.read_dir(){
+            Ok(dir)=>dir,
+            // a new root has no processes yet
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound=>return Ok(()),
+            Err(e)=>return Err(Error::io(root, "list the processes", e)),
+        };
+        let mut found = Vec::new();
+        for entry in dir{
+            let path = entry.map_err(|e|Error::io(root, "list the processes", e))?.path();
+            if path.is_dir() && path.join(PROCESS_SETTING_FILE_NAME).exists(){
+                found.push(path.file_name().map(|name|name.to_string_lossy().into_owned()).unwrap_or_default());
+            }
+        }
+        found.sort();
+        for name in found{
+            self.add_process(&name);
+        }
+        Ok(())
+    }
+}
+
+// set the status of a process in project.json of root
+pub fn update_process_status(root: &Path, name: &str, status: ProcessStatus)->Result<()>{
+    ProjectManifest::update(root, |manifest|manifest.set_status(name, status))
+}
@@ -0,0 +1,152 @@
+// upgrades of older settings files. every document carries a schema_version,
+// files without one are version 0. a document is brought up to date one version at a time
+// while it is still plain json, before it is deserialized.
+use std::path::Path;
+use serde_json::{Map, Value};
+use super::super::error::{Error, Result};
+
+pub const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+// the files with a schema
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Document{
+    // texshooter.json
+    Project,
+    // process.json
+    Process,
+    // caribration.json
+    Caribration,
+    // project.json
+    Manifest,
+}
+
+type Migration = fn(&mut Map<String, Value>);
+
+impl Document{
+    // migrations()[n] upgrades version n to n + 1
+    fn migrations(&self)->&'static [Migration]{
+        match self{
+            Document::Project=>&[project_v0_to_v1],
+            Document::Process=>&[process_v0_to_v1],
+            Document::Caribration=>&[caribration_v0_to_v1],
+            Document::Manifest=>&[manifest_v0_to_v1],
+        }
+    }
+    // the version this build writes
+    pub fn current_version(&self)->u32{
+        self.migrations().len() as u32
+    }
+}
+
+// bring document, read from path, to the current version of kind
+pub fn migrate(kind: Document, mut document: Value, path: &Path)->Result<Value>{
+    let object = document.as_object_mut()
+        .ok_or_else(||Error::validation("load settings", &format!("{} is not a json object", path.display())))?;
+    let version = match object.get(SCHEMA_VERSION_KEY){
+        None=>0,
+        Some(version)=>version.as_u64()
+            .ok_or_else(||Error::validation("load settings", &format!("{} has an invalid {}: {}", path.display(), SCHEMA_VERSION_KEY, version)))?,
+    };
+    let current = kind.current_version() as u64;
+    if version > current{
+        return Err(Error::validation("load settings", &format!(
+            "{} has schema version {}, this version of tex_shooter reads up to {}. please update tex_shooter",
+            path.display(), version, current
+        )));
+    }
+    for migration in &kind.migrations()[version as usize..]{
+        migration(object);
+    }
+    object.insert(SCHEMA_VERSION_KEY.to_string(), Value::from(current));
+    Ok(document)
+}
+
+// version 0 is the first versioned shape of texshooter.json, the exposure values were already strings
+// such as "100", "5.6" and "1/15". nothing changes but the version.
+fn project_v0_to_v1(_project: &mut Map<String, Value>){
+}
+
+// the default trim points of version 0 repeated the top left corner, they were no quad.
+// the full frame they stood for is written as it is now, so it does not count as trim points set by hand.
+const DEGENERATE_TRIM_POINTS: [(f64, f64); 4] = [(0.0, 1.0), (0.0, 0.0), (0.0, 1.0), (1.0, 1.0)];
+const FULL_FRAME_TRIM_POINTS: [(f64, f64); 4] = [(0.0, 1.0), (0.0, 0.0), (1.0, 0.0), (1.0, 1.0)];
+
+fn process_v0_to_v1(process: &mut Map<String, Value>){
+    let points = match process.get_mut("options").and_then(|options|options.get_mut("trim_point")){
+        Some(points)=>points,
+        None=>return,
+    };
+    let is_degenerate = points.as_array().is_some_and(|array|{
+        array.len() == 4 && array.iter().zip(DEGENERATE_TRIM_POINTS.iter()).all(|(point, (x, y))|{
+            point.as_array().is_some_and(|p|p.len() == 2 && p[0].as_f64() == Some(*x) && p[1].as_f64() == Some(*y))
+        })
+    });
+    if is_degenerate{
+        *points = Value::from(FULL_FRAME_TRIM_POINTS.iter().map(|(x, y)|Value::from(vec![*x, *y])).collect::<Vec<_>>());
+    }
+}
+
+// the tele end was spelled tale
+fn caribration_v0_to_v1(caribration: &mut Map<String, Value>){
+    for (old, new) in [("focal_length_tale", "focal_length_tele"), ("mat_tale", "mat_tele"), ("dist_tale", "dist_tele")].iter(){
+        if let Some(value) = caribration.remove(*old){
+            caribration.insert(new.to_string(), value);
+        }
+    }
+}
+
+// project.json came after the versioning, a file without a version is the first one
+fn manifest_v0_to_v1(_manifest: &mut Map<String, Value>){
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use serde_json::json;
+
+    fn path()->&'static Path{
+        Path::new("process.json")
+    }
+
+    #[test]
+    fn process_v0_gets_full_frame_trim_points(){
+        let v0 = json!({"options": {"trim_point": [[0.0, 1.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]], "auto_trimming": true}});
+        let migrated = migrate(Document::Process, v0, path()).unwrap();
+        assert_eq!(migrated, json!({
+            "schema_version": 1,
+            "options": {"trim_point": [[0.0, 1.0], [0.0, 0.0], [1.0, 0.0], [1.0, 1.0]], "auto_trimming": true},
+        }));
+    }
+
+    #[test]
+    fn process_v0_keeps_trim_points_set_by_hand(){
+        let points = json!([[0.1, 0.9], [0.1, 0.1], [0.9, 0.1], [0.9, 0.9]]);
+        let migrated = migrate(Document::Process, json!({"options": {"trim_point": points}}), path()).unwrap();
+        assert_eq!(migrated["options"]["trim_point"], points);
+        // a process without options is only versioned
+        assert_eq!(migrate(Document::Process, json!({}), path()).unwrap(), json!({"schema_version": 1}));
+    }
+
+    #[test]
+    fn caribration_v0_renames_the_tele_end(){
+        let v0 = json!({"focal_length_tale": 70.0, "mat_tale": [1.0], "dist_tale": [], "mat_wide": [2.0]});
+        let migrated = migrate(Document::Caribration, v0, path()).unwrap();
+        assert_eq!(migrated, json!({"schema_version": 1, "focal_length_tele": 70.0, "mat_tele": [1.0], "dist_tele": [], "mat_wide": [2.0]}));
+    }
+
+    #[test]
+    fn project_v0_only_gets_a_version(){
+        let v0 = json!({"iso": "100", "av": "5.6", "tv": "1/15"});
+        let migrated = migrate(Document::Project, v0, path()).unwrap();
+        assert_eq!(migrated, json!({"schema_version": 1, "iso": "100", "av": "5.6", "tv": "1/15"}));
+        // the current version is left alone
+        assert_eq!(migrate(Document::Project, migrated.clone(), path()).unwrap(), migrated);
+    }
+
+    #[test]
+    fn migrate_rejects_what_it_cannot_read(){
+        assert!(matches!(migrate(Document::Manifest, json!({"schema_version": 2}), path()), Err(Error::Validation{..})));
+        assert!(matches!(migrate(Document::Manifest, json!({"schema_version": "1"}), path()), Err(Error::Validation{..})));
+        assert!(matches!(migrate(Document::Manifest, json!([1, 2]), path()), Err(Error::Validation{..})));
+    }
+}
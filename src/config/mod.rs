@@ -6,79 +6,40 @@ use std::io::BufReader;
 use serde::{de, Deserialize, Serialize};
 //use uuid::Uuid;
 //use chrono::DateTime;
-use super::camera;
+use super::exposure::Exposure;
+use super::error::{Error, Result};
 
-pub fn save_to_json<T: Serialize>(instance: &T, file_name: &Path)->bool{
-    let serialized = serde_json::to_string(instance);
-    if serialized.is_ok(){
-        let result = std::fs::write(file_name, &serialized.unwrap());
-        return result.is_ok();
-    }
-    return false;
-}
+pub mod migration;
+pub mod manifest;
+pub mod lifecycle;
+use migration::Document;
+use manifest::ProjectManifest;
 
-pub fn load_from_json<T: de::DeserializeOwned>(instance: &mut T, file_name: &Path)->bool{
-    let file = File::open(file_name);
-    if file.is_ok(){
-        let reader = BufReader::new(file.unwrap());
-        let deserialized = serde_json::from_reader(reader);
-        if deserialized.is_ok(){
-            *instance = deserialized.unwrap();
-            return true;
-        }
-    }
-    false
+pub fn save_to_json<T: Serialize>(instance: &T, file_name: &Path)->Result<()>{
+    let serialized = serde_json::to_string(instance).map_err(|e|Error::json(file_name, "save settings", e))?;
+    std::fs::write(file_name, &serialized).map_err(|e|Error::io(file_name, "save settings", e))
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct RationalValue{
-    numarator : u32,
-    denominator : u32,
+pub fn load_from_json<T: de::DeserializeOwned>(instance: &mut T, file_name: &Path)->Result<()>{
+    let file = File::open(file_name).map_err(|e|Error::io(file_name, "load settings", e))?;
+    *instance = serde_json::from_reader(BufReader::new(file)).map_err(|e|Error::json(file_name, "load settings", e))?;
+    Ok(())
 }
 
-impl ToString for RationalValue{
-    fn to_string(&self)->String{
-        if self.denominator == 1{
-            self.numarator.to_string()
-        }
-        else{
-            format!("{}/{}", self.numarator, self.denominator)
-        }
-    }
+// like load_from_json for a document with a schema, older versions are migrated on the way
+pub fn load_versioned_json<T: de::DeserializeOwned>(instance: &mut T, file_name: &Path, kind: Document)->Result<()>{
+    let file = File::open(file_name).map_err(|e|Error::io(file_name, "load settings", e))?;
+    let document = serde_json::from_reader(BufReader::new(file)).map_err(|e|Error::json(file_name, "load settings", e))?;
+    *instance = parse_versioned(document, file_name, kind)?;
+    Ok(())
 }
 
-impl RationalValue{
-    pub fn from_str(value : &str)->Self{
-        let mut v = RationalValue{
-            numarator:1,
-            denominator:1,
-        };
-        v.set_from_str(value);
-        return v;
-    }
-    pub fn set_from_str(&mut self, value : &str){
-        if value.find("/").is_some(){
-            let vec : Vec<&str> = value.split("/").collect();
-            if vec.len() == 2{
-                let v0 = vec[0].parse();
-                let v1 = vec[1].parse();
-                if v0.is_ok() && v1.is_ok(){
-                    self.numarator = v0.unwrap();
-                    self.denominator = v1.unwrap();
-                }
-            }
-        }
-        else{
-            let v = value.parse();
-            if v.is_ok(){
-                self.numarator = v.unwrap();
-                self.denominator = 1;
-            }
-        }
-    }
+fn parse_versioned<T: de::DeserializeOwned>(document: serde_json::Value, file_name: &Path, kind: Document)->Result<T>{
+    let document = migration::migrate(kind, document, file_name)?;
+    serde_json::from_value(document).map_err(|e|Error::json(file_name, "load settings", e))
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ExposureSettings{
     iso: String,
     aperture_value: String,
@@ -95,39 +56,64 @@ impl ExposureSettings{
     pub fn new()->Self{
         Default::default()
     }
-    pub fn calc_ev(&self)->f32{
-        self.calc_iso() + self.calc_av() + self.calc_tv()
+    pub fn to_exposure(&self)->Result<Exposure>{
+        Exposure::parse(&self.iso, &self.aperture_value, &self.time_value).map_err(|message|Error::validation("read the exposure", &message))
+    }
+    pub fn from_exposure(exposure: &Exposure)->Self{
+        ExposureSettings{
+            iso: exposure.iso.label().to_string(),
+            aperture_value: exposure.av.label().to_string(),
+            time_value: exposure.tv.label().to_string()
+        }
+    }
+    pub fn get_iso(&self)->&str{
+        &self.iso
     }
-    fn calc_iso(&self)->f32{
-        let v : f32 = self.iso.parse().unwrap();
-        return (v / 100.0).log2();
+    pub fn get_aperture_value(&self)->&str{
+        &self.aperture_value
     }
-    fn calc_av(&self)->f32{
-        let v : f32 = self.aperture_value.parse().unwrap();
-        return v.powi(2).log2();
+    pub fn get_time_value(&self)->&str{
+        &self.time_value
     }
-    fn calc_tv(&self)->f32{
-        let tmp = RationalValue::from_str(&self.time_value);
-        let denominator = camera::convert_tv_denominator(tmp.denominator);
-        let v = tmp.numarator as f32 / denominator as f32;
-        return -v.log2();
+    // exposure value at ISO 100, see Exposure::ev100
+    pub fn calc_ev(&self)->Result<f32>{
+        Ok(self.to_exposure()?.ev100())
     }
 }
 
-const PROCESS_SETTING_FILE_NAME: &str = "process.json";
+pub const PROCESS_SETTING_FILE_NAME: &str = "process.json";
 
-#[derive(Serialize, Deserialize, Debug)]
+// number of light directions shot per process
+pub const TAKE_COUNT: usize = 8;
+// gray card reference shot in a process directory
+pub const GRAY_FILE_NAME: &str = "gray.jpg";
+
+// directional take in a process directory
+pub fn take_file_name(index: usize)->String{
+    format!("take_{}.jpg", index)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ProcessingOptions{
     auto_trimming: bool,
+    // corners of the sample in 0..1 frame coordinates (y down),
+    // bottom-left, top-left, top-right, bottom-right
     trim_point: [(f32, f32);4],
+    // edge length in pixels of the trimmed square
+    #[serde(default = "default_trim_resolution")]
+    trim_resolution: u32,
     tiling: bool,
     tiling_blend: f32,
 }
+fn default_trim_resolution()->u32{
+    2048
+}
 impl Default for ProcessingOptions {
     fn default()->Self {
         ProcessingOptions{
             auto_trimming: true,
-            trim_point: [(0.0, 1.0), (0.0, 0.0), (0.0, 1.0), (1.0, 1.0)],
+            trim_point: [(0.0, 1.0), (0.0, 0.0), (1.0, 0.0), (1.0, 1.0)],
+            trim_resolution: default_trim_resolution(),
             tiling: true,
             tiling_blend: 0.1
         }
@@ -137,26 +123,61 @@ impl ProcessingOptions{
     pub fn new()->Self{
         Default::default()
     }
+    pub fn get_auto_trimming(&self)->bool{
+        self.auto_trimming
+    }
+    pub fn set_auto_trimming(&mut self, auto_trimming: bool){
+        self.auto_trimming = auto_trimming;
+    }
+    pub fn get_trim_points(&self)->&[(f32, f32);4]{
+        &self.trim_point
+    }
+    pub fn set_trim_points(&mut self, points: [(f32, f32);4]){
+        self.trim_point = points;
+    }
+    // false while the trim points are still the full frame
+    pub fn has_trim_points(&self)->bool{
+        self.trim_point != ProcessingOptions::default().trim_point
+    }
+    pub fn get_trim_resolution(&self)->u32{
+        self.trim_resolution
+    }
+    pub fn get_tiling(&self)->bool{
+        self.tiling
+    }
+    // fraction of the width and height cross blended into the opposite edge
+    pub fn get_tiling_blend(&self)->f32{
+        self.tiling_blend
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct LensCaribrationInfo{
+    schema_version: u32,
     focal_length_wide: f32,
-    focal_length_tale: f32,
+    focal_length_tele: f32,
     mat_wide: Vec<f32>,
     dist_wide: Vec<f32>,
-    mat_tale: Vec<f32>,
-    dist_tale: Vec<f32>,
+    mat_tele: Vec<f32>,
+    dist_tele: Vec<f32>,
+    // size of the checkerboard images in pixels, 0 in calibrations stored before it was recorded
+    #[serde(default)]
+    image_width: u32,
+    #[serde(default)]
+    image_height: u32,
 }
 impl Default for LensCaribrationInfo {
     fn default()->Self {
         LensCaribrationInfo{
+            schema_version: Document::Caribration.current_version(),
             focal_length_wide: 0.0,
-            focal_length_tale: 0.0,
+            focal_length_tele: 0.0,
             mat_wide: Vec::new(),
             dist_wide: Vec::new(),
-            mat_tale: Vec::new(),
-            dist_tale: Vec::new()
+            mat_tele: Vec::new(),
+            dist_tele: Vec::new(),
+            image_width: 0,
+            image_height: 0
         }
     }
 }
@@ -164,29 +185,108 @@ impl LensCaribrationInfo{
     pub fn new()->Self{
         Default::default()
     }
-    pub fn save(&self, file_name: &Path)->bool{
-        return save_to_json(self, file_name);
+    pub fn save(&self, file_name: &Path)->Result<()>{
+        save_to_json(self, file_name)
+    }
+    pub fn load(&mut self, file_name: &Path)->Result<()>{
+        load_versioned_json(self, file_name, Document::Caribration)
+    }
+    pub fn get_focal_length_wide(&self)->f32{
+        self.focal_length_wide
+    }
+    pub fn get_focal_length_tele(&self)->f32{
+        self.focal_length_tele
+    }
+    // 3x3 row major camera matrix and (k1, k2, p1, p2, k3) of the wide end
+    pub fn get_wide(&self)->(&[f32], &[f32]){
+        (&self.mat_wide, &self.dist_wide)
+    }
+    pub fn get_tele(&self)->(&[f32], &[f32]){
+        (&self.mat_tele, &self.dist_tele)
+    }
+    pub fn set_wide(&mut self, focal_length: f32, mat: Vec<f32>, dist: Vec<f32>){
+        self.focal_length_wide = focal_length;
+        self.mat_wide = mat;
+        self.dist_wide = dist;
     }
-    pub fn load(&mut self, file_name: &Path)->bool{
-        return load_from_json(self, file_name);
+    pub fn set_tele(&mut self, focal_length: f32, mat: Vec<f32>, dist: Vec<f32>){
+        self.focal_length_tele = focal_length;
+        self.mat_tele = mat;
+        self.dist_tele = dist;
+    }
+    // the camera matrices are in pixels of this size, None when it is not known
+    pub fn get_image_size(&self)->Option<(u32, u32)>{
+        if self.image_width > 0 && self.image_height > 0 { Some((self.image_width, self.image_height)) } else { None }
+    }
+    pub fn set_image_size(&mut self, width: u32, height: u32){
+        self.image_width = width;
+        self.image_height = height;
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+// lens calibration of a project, stored in the project root
+pub const LENS_CARIBRATION_FILE_NAME: &str = "caribration.json";
+
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct LensInfo{
     name: String,       // lens name at the time of shooting
     focal_length: f32   // focal length at the time of shooting
 }
+impl LensInfo{
+    pub fn get_name(&self)->&str{
+        &self.name
+    }
+    pub fn get_focal_length(&self)->f32{
+        self.focal_length
+    }
+}
 
-#[derive(Serialize, Deserialize, Debug)]
+// capture state of a process, stored next to process.json
+pub const PROCESS_STATE_FILE_NAME: &str = "state.json";
+// intermediate results of processing, see processing::cache
+pub const PROCESS_CACHE_DIR_NAME: &str = "cache";
+
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct ProcessingState{
     lens: LensInfo,
     has_gray: bool,
-    has_takes: [bool; 8]
+    has_takes: [bool; TAKE_COUNT]
+}
+impl ProcessingState{
+    pub fn new()->Self{
+        Default::default()
+    }
+    pub fn save(&self, file_name: &Path)->Result<()>{
+        save_to_json(self, file_name)
+    }
+    pub fn load(&mut self, file_name: &Path)->Result<()>{
+        load_from_json(self, file_name)
+    }
+    pub fn get_lens(&self)->&LensInfo{
+        &self.lens
+    }
+    pub fn set_focal_length(&mut self, focal_length: f32){
+        self.lens.focal_length = focal_length;
+    }
+    pub fn has_gray(&self)->bool{
+        self.has_gray
+    }
+    pub fn set_gray(&mut self, has_gray: bool){
+        self.has_gray = has_gray;
+    }
+    pub fn has_take(&self, index: usize)->bool{
+        self.has_takes.get(index).cloned().unwrap_or(false)
+    }
+    pub fn set_take(&mut self, index: usize, has_take: bool){
+        if let Some(take) = self.has_takes.get_mut(index){
+            *take = has_take;
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ProcessingSettings{
+    schema_version: u32,
     description: String,
     exposure: ExposureSettings,
     options: ProcessingOptions,
@@ -194,6 +294,7 @@ pub struct ProcessingSettings{
 impl Default for ProcessingSettings {
     fn default()->Self {
         ProcessingSettings{
+            schema_version: Document::Process.current_version(),
             description: "".to_string(),
             exposure: ExposureSettings::new(),
             options: ProcessingOptions::new()
@@ -204,157 +305,207 @@ impl ProcessingSettings{
     pub fn new()->Self{
         Default::default()
     }
-    pub fn save(&self, file_name: &Path)->bool{
-        return save_to_json(self, file_name);
+    pub fn save(&self, file_name: &Path)->Result<()>{
+        save_to_json(self, file_name)
+    }
+    pub fn load(&mut self, file_name: &Path)->Result<()>{
+        load_versioned_json(self, file_name, Document::Process)
+    }
+    pub fn get_exposure(&self)->&ExposureSettings{
+        &self.exposure
+    }
+    pub fn set_exposure(&mut self, exposure: ExposureSettings){
+        self.exposure = exposure;
     }
-    pub fn load(&mut self, file_name: &Path)->bool{
-        return load_from_json(self, file_name);
+    pub fn get_options(&self)->&ProcessingOptions{
+        &self.options
+    }
+    pub fn get_options_mut(&mut self)->&mut ProcessingOptions{
+        &mut self.options
+    }
+}
+
+// how the light direction is switched between takes
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LightControllerKind{
+    // a fixed rig, nothing to switch
+    None,
+    // the operator moves the light when asked
+    Manual,
+    // an LED ring on a serial port
+    Serial,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LightSettings{
+    controller: LightControllerKind,
+    // e.g. COM3 or /dev/ttyACM0
+    port: String,
+    baud_rate: u32,
+}
+impl Default for LightSettings {
+    fn default()->Self {
+        LightSettings{
+            controller: LightControllerKind::None,
+            port: "".to_string(),
+            baud_rate: 115200
+        }
+    }
+}
+impl LightSettings{
+    pub fn new(controller: LightControllerKind, port: &str, baud_rate: u32)->Self{
+        LightSettings{controller, port: port.to_string(), baud_rate}
+    }
+    pub fn get_controller(&self)->LightControllerKind{
+        self.controller
+    }
+    pub fn get_port(&self)->&str{
+        &self.port
+    }
+    pub fn get_baud_rate(&self)->u32{
+        self.baud_rate
     }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ProjectSettings{
+    schema_version: u32,
     root_path: String,
     last_exposure: ExposureSettings,
-    last_processing: String
+    last_processing: String,
+    #[serde(default)]
+    lights: LightSettings
 }
 
 impl ProjectSettings{
-    fn get_json_path()->Result<std::path::PathBuf, String>{
-        let path = dirs::home_dir();
-        if path.is_some(){
-            let mut path = path.unwrap();
-            path.push("texshooter.json");
-            return Ok(path);
-        }
-        Err(String::from("could not get a home directory."))
-    }
-    pub fn load()->Result<ProjectSettings, String>{
+    fn get_json_path()->Result<std::path::PathBuf>{
+        let mut path = dirs::home_dir().ok_or_else(||{
+            let source = std::io::Error::new(std::io::ErrorKind::NotFound, "could not get a home directory");
+            Error::io(Path::new("texshooter.json"), "find the settings", source)
+        })?;
+        path.push("texshooter.json");
+        Ok(path)
+    }
+    // the settings of a first start, the project lives in ~/texshooter
+    pub fn new()->ProjectSettings{
+        let mut path = dirs::home_dir().unwrap_or_default();
+        path.push("texshooter");
+        ProjectSettings::with_root(&path.to_string_lossy())
+    }
+    pub fn load()->Result<ProjectSettings>{
         // try to read a config
-        let path = ProjectSettings::get_json_path();
-        if path.is_ok(){
-            let mut path = path.unwrap();
-            let json = std::fs::read_to_string(&path);
-            if json.is_ok(){
-                let json = json.unwrap();
-                let deserialized = serde_json::from_str(&json);
-                if deserialized.is_ok(){
-                    let settings : ProjectSettings = deserialized.unwrap();
-                    return Ok(settings);
-                }
-                return Err(String::from("failed to deserialize settings"));
-            }
-            else{
-                path.pop();
-                path.push("texshooter");
-                return Ok(ProjectSettings{
-                    root_path: path.into_os_string().into_string().unwrap(),
-                    //root_path: path.as_path().display().to_string(),
-                    last_exposure: ExposureSettings::new(),
-                    last_processing: "".to_string()
-                });
+        let path = ProjectSettings::get_json_path()?;
+        match std::fs::read_to_string(&path){
+            Ok(json)=>{
+                let document = serde_json::from_str(&json).map_err(|e|Error::json(&path, "load settings", e))?;
+                parse_versioned(document, &path, Document::Project)
             }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound=>Ok(ProjectSettings::new()),
+            Err(e)=>Err(Error::io(&path, "load settings", e)),
         }
-        Err(path.unwrap_err())
-    }
-    pub fn save(&self){
-        let serialized = serde_json::to_string(self);
-        if serialized.is_ok(){
-            let path = ProjectSettings::get_json_path();
-            if path.is_ok(){
-                let _ = std::fs::write(&path.unwrap(), &serialized.unwrap());
-            }
+    }
+    // settings for a given root without touching texshooter.json
+    pub fn with_root(root_path: &str)->ProjectSettings{
+        ProjectSettings{
+            schema_version: Document::Project.current_version(),
+            root_path: root_path.to_string(),
+            last_exposure: ExposureSettings::new(),
+            last_processing: "".to_string(),
+            lights: LightSettings::default()
         }
     }
+    pub fn save(&self)->Result<()>{
+        save_to_json(self, &ProjectSettings::get_json_path()?)
+    }
     pub fn get_root_path(&self)->&str{
         self.root_path.as_str()
     }
     pub fn set_root_path(&mut self, path: &str){
         self.root_path = path.to_string();
     }
-    pub fn get_iso(&self)->u32{
-        self.last_exposure.iso.parse().unwrap()
-    }
-    pub fn get_iso_as_str(&self)->&str{
-        &self.last_exposure.iso
-    }
-    pub fn set_iso(&mut self, iso: &str){
-        self.last_exposure.iso = iso.to_string();
-    }
-    pub fn get_aperture_value(&self)->f32{
-        self.last_exposure.aperture_value.parse().unwrap()
-    }
-    pub fn get_aperture_value_as_str(&self)->&str{
-        &self.last_exposure.aperture_value
+    pub fn get_last_exposure(&self)->&ExposureSettings{
+        &self.last_exposure
     }
-    pub fn set_aperture_value(&mut self, av: &str){
-        self.last_exposure.aperture_value = av.to_string();
+    pub fn set_last_exposure(&mut self, exposure: ExposureSettings){
+        self.last_exposure = exposure;
     }
-    pub fn get_time_value(&self)->RationalValue{
-        RationalValue::from_str(&self.last_exposure.time_value)
+    pub fn set_last_processing(&mut self, process_name: &str)->Result<()>{
+        if !self.exist_process(process_name){
+            return Err(Error::validation("select the process", &format!("{} is not a process in {}", process_name, self.root_path)));
+        }
+        self.last_processing = process_name.to_string();
+        Ok(())
     }
-    pub fn get_time_value_as_str(&self)->&str{
-        &self.last_exposure.time_value
+    pub fn get_last_processing(&self)->&str{
+        &self.last_processing
     }
-    pub fn set_time_value(&mut self, tv: &str){
-        self.last_exposure.time_value = tv.to_string();
+    pub fn get_lights(&self)->&LightSettings{
+        &self.lights
     }
-    pub fn set_last_processing(&mut self, process_name: &str)->bool{
-        if self.exist_process(process_name){
-            self.last_processing = process_name.to_string();
-            return true;
-        }
-        false
-    }
-    pub fn calc_process_list(&self)->Vec<String>{
-        return Self::enum_subdir(&PathBuf::from(&self.root_path));
-    }
-    fn enum_subdir(dir_path: &Path)->Vec<String>{
-        let mut process = Vec::new();
-        let root_dir = dir_path.read_dir();
-        if root_dir.is_ok(){
-            let root_dir = root_dir.unwrap();
-            for entry in root_dir{
-                let path = entry.unwrap().path();
-                if path.is_dir(){
-                    let mut buf = path.to_path_buf();
-                    buf.push(PROCESS_SETTING_FILE_NAME);
-                    if buf.exists(){
-                        process.push(path.into_os_string().into_string().unwrap());
-                    }
-                }
-            }
-        }
-        process
+    pub fn set_lights(&mut self, lights: LightSettings){
+        self.lights = lights;
     }
-    pub fn create_process(&self, process_name: &str)->Option<ProcessingSettings>{
+    pub fn get_process_path(&self, process_name: &str)->PathBuf{
+        let mut path = PathBuf::from(&self.root_path);
+        path.push(process_name);
+        path
+    }
+    // the processes indexed in project.json of the root, as full paths
+    pub fn calc_process_list(&self)->Result<Vec<String>>{
+        Ok(ProjectManifest::read_processes(Path::new(&self.root_path))?.iter()
+            .map(|entry|self.get_process_path(entry.get_name()).to_string_lossy().into_owned())
+            .collect())
+    }
+    // project.json of the root as it is, None while the project has none
+    pub fn read_manifest(&self)->Result<Option<ProjectManifest>>{
+        ProjectManifest::read(Path::new(&self.root_path))
+    }
+    // change project.json of the root, see ProjectManifest::update
+    pub fn update_manifest<T, F>(&self, change: F)->Result<T>
+        where F: FnOnce(&mut ProjectManifest)->Result<T>
+    {
+        ProjectManifest::update(Path::new(&self.root_path), change)
+    }
+    pub fn create_process(&self, process_name: &str)->Result<ProcessingSettings>{
+        lifecycle::validate_process_name(process_name)?;
         let mut path = PathBuf::from(&self.root_path);
         path.push(process_name);
         if !path.exists() {
-            let result = fs::create_dir(&path);
-            if result.is_err(){
-                return None;
-            }
+            fs::create_dir(&path).map_err(|e|Error::io(&path, "create the process", e))?;
         }
         path.push(PROCESS_SETTING_FILE_NAME);
         let mut settings = ProcessingSettings::new();
         if !path.exists(){
-            settings.save(&path);
-            return Some(settings);
+            settings.save(&path)?;
         }
-        if settings.load(&path){
-            return Some(settings);
+        else{
+            settings.load(&path)?;
         }
-        None
+        self.update_manifest(|manifest|{
+            manifest.add_process(process_name);
+            Ok(())
+        })?;
+        Ok(settings)
+    }
+    pub fn load_process(&self, process_name: &str)->Result<ProcessingSettings>{
+        let mut path = self.get_process_path(process_name);
+        path.push(PROCESS_SETTING_FILE_NAME);
+        let mut settings = ProcessingSettings::new();
+        settings.load(&path)?;
+        Ok(settings)
     }
     fn exist_process(&self, process_name: &str)->bool{
+        if lifecycle::validate_process_name(process_name).is_err(){
+            return false;
+        }
         let mut path = PathBuf::from(&self.root_path);
         path.push(process_name);
         if !path.exists(){
             return false;
         }
         path.push(PROCESS_SETTING_FILE_NAME);
-        return path.exists();
+        path.exists()
     }
 }
 
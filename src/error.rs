@@ -0,0 +1,86 @@
+// the error of the crate. every variant says what was being done, file errors also say which file.
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum Error{
+    // a file or directory could not be read or written
+    Io{path: PathBuf, context: String, source: std::io::Error},
+    // a json file could not be parsed or written
+    Json{path: PathBuf, context: String, source: serde_json::Error},
+    // an image file could not be decoded or encoded
+    Image{path: PathBuf, context: String, source: image::ImageError},
+    // the camera or its sdk refused
+    Camera{context: String, message: String},
+    // the light ring or a step of shooting failed, e.g. the gray card could not be metered
+    Capture{context: String, message: String},
+    // the frames could not be turned into maps, e.g. too few takes
+    Processing{context: String, message: String},
+    // the operator cancelled the job
+    Cancelled,
+    // a value or a request that makes no sense, e.g. an unknown shutter speed
+    Validation{context: String, message: String},
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error{
+    pub fn io(path: &Path, context: &str, source: std::io::Error)->Self{
+        Error::Io{path: path.to_path_buf(), context: context.to_string(), source}
+    }
+    pub fn json(path: &Path, context: &str, source: serde_json::Error)->Self{
+        Error::Json{path: path.to_path_buf(), context: context.to_string(), source}
+    }
+    pub fn image(path: &Path, context: &str, source: image::ImageError)->Self{
+        Error::Image{path: path.to_path_buf(), context: context.to_string(), source}
+    }
+    pub fn camera(context: &str, message: &str)->Self{
+        Error::Camera{context: context.to_string(), message: message.to_string()}
+    }
+    pub fn capture(context: &str, message: &str)->Self{
+        Error::Capture{context: context.to_string(), message: message.to_string()}
+    }
+    pub fn processing(context: &str, message: &str)->Self{
+        Error::Processing{context: context.to_string(), message: message.to_string()}
+    }
+    pub fn validation(context: &str, message: &str)->Self{
+        Error::Validation{context: context.to_string(), message: message.to_string()}
+    }
+    // title of the error dialog in the ui
+    pub fn title(&self)->String{
+        match self{
+            Error::Io{context, ..} | Error::Json{context, ..} | Error::Image{context, ..} | Error::Camera{context, ..}
+                | Error::Capture{context, ..} | Error::Processing{context, ..} | Error::Validation{context, ..}=>{
+                format!("failed to {}", context)
+            }
+            Error::Cancelled=>"cancelled".to_string(),
+        }
+    }
+    // what went wrong, without the context
+    pub fn message(&self)->String{
+        match self{
+            Error::Io{path, source, ..}=>format!("{}: {}", path.display(), source),
+            Error::Json{path, source, ..}=>format!("{}: {}", path.display(), source),
+            Error::Image{path, source, ..}=>format!("{}: {}", path.display(), source),
+            Error::Camera{message, ..} | Error::Capture{message, ..} | Error::Processing{message, ..} | Error::Validation{message, ..}=>message.clone(),
+            Error::Cancelled=>"the operator cancelled the job".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Error{
+    fn fmt(&self, f: &mut fmt::Formatter)->fmt::Result{
+        write!(f, "{}: {}", self.title(), self.message())
+    }
+}
+
+impl std::error::Error for Error{
+    fn source(&self)->Option<&(dyn std::error::Error + 'static)>{
+        match self{
+            Error::Io{source, ..}=>Some(source),
+            Error::Json{source, ..}=>Some(source),
+            Error::Image{source, ..}=>Some(source),
+            _=>None,
+        }
+    }
+}
@@ -0,0 +1,292 @@
+// exposure in APEX units. every value is kept as a whole number of sixth stops, which holds both the
+// third and the half stop series, so nominal labels like 1/15 or f/6.7 give the exact stop they stand for (Tv 4, Av 5.5).
+use std::fmt;
+
+// step between neighbouring values of a series
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Increment{
+    Full,
+    Half,
+    Third,
+}
+
+// labels and their offset in sixth stops, in order.
+// iso is counted from ISO 100 (Sv 5), aperture from f/1.0 (Av 0), shutter speed from 1s (Tv 0).
+// a half stop whose label is also a third stop (f/4.5, 1/10) is left out, a label stands for one value.
+const ISO_TABLE: &[(&str, i32)] = &[
+    ("50", -6), ("64", -4), ("80", -2), ("100", 0), ("125", 2), ("160", 4), ("200", 6), ("250", 8),
+    ("320", 10), ("400", 12), ("500", 14), ("640", 16), ("800", 18), ("1000", 20), ("1250", 22), ("1600", 24),
+    ("2000", 26), ("2500", 28), ("3200", 30), ("4000", 32), ("5000", 34), ("6400", 36), ("8000", 38), ("10000", 40),
+    ("12800", 42), ("16000", 44), ("20000", 46), ("25600", 48), ("32000", 50), ("40000", 52), ("51200", 54), ("64000", 56),
+    ("80000", 58), ("102400", 60),
+];
+const AV_TABLE: &[(&str, i32)] = &[
+    ("1.0", 0), ("1.1", 2), ("1.2", 4), ("1.4", 6), ("1.6", 8), ("1.8", 10), ("2.0", 12), ("2.2", 14),
+    ("2.5", 16), ("2.8", 18), ("3.2", 20), ("3.5", 22), ("4.0", 24), ("4.5", 26), ("5.0", 28), ("5.6", 30),
+    ("6.3", 32), ("6.7", 33), ("7.1", 34), ("8.0", 36), ("9.0", 38), ("9.5", 39), ("10.0", 40), ("11.0", 42),
+    ("13.0", 44), ("14.0", 46), ("16.0", 48), ("18.0", 50), ("19.0", 51), ("20.0", 52), ("22.0", 54), ("25.0", 56),
+    ("27.0", 57), ("29.0", 58), ("32.0", 60), ("36.0", 62), ("38.0", 63), ("40.0", 64), ("45.0", 66), ("51.0", 68),
+    ("54.0", 69), ("57.0", 70), ("64.0", 72), ("72.0", 74), ("76.0", 75), ("80.0", 76), ("91.0", 78),
+];
+const TV_TABLE: &[(&str, i32)] = &[
+    ("30", -30), ("25", -28), ("20", -26), ("15", -24), ("13", -22), ("10", -20), ("8", -18), ("6", -16),
+    ("5", -14), ("4", -12), ("3.2", -10), ("3", -9), ("2.5", -8), ("2", -6), ("1.6", -4), ("1.5", -3),
+    ("1.3", -2), ("1", 0), ("0.8", 2), ("0.7", 3), ("0.6", 4), ("0.5", 6), ("0.4", 8), ("0.3", 10),
+    ("1/4", 12), ("1/5", 14), ("1/6", 16), ("1/8", 18), ("1/10", 20), ("1/13", 22), ("1/15", 24), ("1/20", 26),
+    ("1/25", 28), ("1/30", 30), ("1/40", 32), ("1/45", 33), ("1/50", 34), ("1/60", 36), ("1/80", 38), ("1/90", 39),
+    ("1/100", 40), ("1/125", 42), ("1/160", 44), ("1/180", 45), ("1/200", 46), ("1/250", 48), ("1/320", 50), ("1/350", 51),
+    ("1/400", 52), ("1/500", 54), ("1/640", 56), ("1/750", 57), ("1/800", 58), ("1/1000", 60), ("1/1250", 62), ("1/1500", 63),
+    ("1/1600", 64), ("1/2000", 66), ("1/2500", 68), ("1/3000", 69), ("1/3200", 70), ("1/4000", 72), ("1/5000", 74), ("1/6000", 75),
+    ("1/6400", 76), ("1/8000", 78),
+];
+
+fn series(table: &[(&str, i32)], increment: Increment)->Vec<i32>{
+    let step = match increment{
+        Increment::Full=>6,
+        Increment::Half=>3,
+        Increment::Third=>2,
+    };
+    table.iter()
+        .map(|(_, sixths)|*sixths)
+        .filter(|sixths|sixths.rem_euclid(step) == 0)
+        .collect()
+}
+
+fn label(table: &[(&'static str, i32)], sixths: i32)->&'static str{
+    table.iter().find(|(_, s)|*s == sixths).map(|(label, _)|*label).unwrap_or("?")
+}
+
+fn contains(table: &[(&str, i32)], sixths: i32)->bool{
+    table.iter().any(|(_, s)|*s == sixths)
+}
+
+// a number such as 5.6 or 1/250
+fn parse_number(value: &str)->Option<f64>{
+    let number: f64 = match value.find('/'){
+        Some(slash)=>{
+            let numerator: f64 = value[..slash].trim().parse().ok()?;
+            let denominator: f64 = value[slash + 1..].trim().parse().ok()?;
+            numerator / denominator
+        }
+        None=>value.parse().ok()?,
+    };
+    if number.is_finite() && number > 0.0 { Some(number) } else { None }
+}
+
+// a label of the table, also written as another number of the same value (8 for 8.0).
+// a value between the labels is an error that names the nearest one, it is never moved there silently.
+// stops gives the position in stops of a parsed number, kind and show name and write a value in the message.
+fn parse(table: &[(&'static str, i32)], value: &str, stops: impl Fn(f64)->f64, kind: &str, show: impl Fn(&str)->String)->Result<i32, String>{
+    let trimmed = value.trim().trim_start_matches("f/").trim_end_matches('"');
+    if let Some((_, sixths)) = table.iter().find(|(label, _)|*label == trimmed){
+        return Ok(*sixths);
+    }
+    let number = parse_number(trimmed).ok_or_else(||format!("{} is not {}", value, kind))?;
+    let same = |label: &str|parse_number(label).is_some_and(|n|(n - number).abs() <= n * 1e-9);
+    if let Some((_, sixths)) = table.iter().find(|(label, _)|same(label)){
+        return Ok(*sixths);
+    }
+    let position = stops(number);
+    let distance = |sixths: i32|(sixths as f64 / 6.0 - position).abs();
+    let nearest = table.iter().min_by(|a, b|distance(a.1).partial_cmp(&distance(b.1)).unwrap_or(std::cmp::Ordering::Equal));
+    match nearest{
+        Some((label, _))=>Err(format!("{} is not {} of the table, the nearest is {}", value, kind, show(label))),
+        None=>Err(format!("{} is not {}", value, kind)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Iso{
+    sixths: i32,
+}
+
+impl Iso{
+    pub fn series(increment: Increment)->Vec<Iso>{
+        series(ISO_TABLE, increment).into_iter().map(|sixths|Iso{sixths}).collect()
+    }
+    pub fn parse(value: &str)->Result<Iso, String>{
+        parse(ISO_TABLE, value, |iso|(iso / 100.0).log2(), "an iso speed", |label|format!("ISO {}", label))
+            .map(|sixths|Iso{sixths})
+    }
+    // the speed as a number, e.g. 1600
+    pub fn speed(&self)->u32{
+        self.label().parse().unwrap_or(0)
+    }
+    // APEX speed value, ISO 100 is 5
+    pub fn sv(&self)->f32{
+        5.0 + self.sixths as f32 / 6.0
+    }
+    pub fn label(&self)->&'static str{
+        label(ISO_TABLE, self.sixths)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aperture{
+    sixths: i32,
+}
+
+impl Aperture{
+    pub fn series(increment: Increment)->Vec<Aperture>{
+        series(AV_TABLE, increment).into_iter().map(|sixths|Aperture{sixths}).collect()
+    }
+    pub fn parse(value: &str)->Result<Aperture, String>{
+        parse(AV_TABLE, value, |f_number|2.0 * f_number.log2(), "an aperture", |label|format!("f/{}", label))
+            .map(|sixths|Aperture{sixths})
+    }
+    // APEX aperture value, log2 of the f-number squared
+    pub fn av(&self)->f32{
+        self.sixths as f32 / 6.0
+    }
+    pub fn label(&self)->&'static str{
+        label(AV_TABLE, self.sixths)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShutterSpeed{
+    sixths: i32,
+}
+
+impl ShutterSpeed{
+    pub fn series(increment: Increment)->Vec<ShutterSpeed>{
+        series(TV_TABLE, increment).into_iter().map(|sixths|ShutterSpeed{sixths}).collect()
+    }
+    pub fn parse(value: &str)->Result<ShutterSpeed, String>{
+        parse(TV_TABLE, value, |seconds|-seconds.log2(), "a shutter speed", |label|label.to_string())
+            .map(|sixths|ShutterSpeed{sixths})
+    }
+    // APEX time value, -log2 of the time in seconds
+    pub fn tv(&self)->f32{
+        self.sixths as f32 / 6.0
+    }
+    // exact exposure time of the stop, e.g. 1/16 s for 1/15
+    pub fn seconds(&self)->f32{
+        (-self.tv()).exp2()
+    }
+    pub fn label(&self)->&'static str{
+        label(TV_TABLE, self.sixths)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Exposure{
+    pub iso: Iso,
+    pub av: Aperture,
+    pub tv: ShutterSpeed,
+}
+
+impl Exposure{
+    pub fn parse(iso: &str, av: &str, tv: &str)->Result<Exposure, String>{
+        Ok(Exposure{iso: Iso::parse(iso)?, av: Aperture::parse(av)?, tv: ShutterSpeed::parse(tv)?})
+    }
+    // APEX exposure value of the aperture and shutter speed, Ev = Av + Tv
+    pub fn ev(&self)->f32{
+        self.av.av() + self.tv.tv()
+    }
+    // exposure value at ISO 100, equal for settings that give the same image brightness
+    pub fn ev100(&self)->f32{
+        (self.av.sixths + self.tv.sixths - self.iso.sixths) as f32 / 6.0
+    }
+    // aperture priority: set av and move the shutter speed so that ev100 stays
+    pub fn with_av(&self, av: Aperture)->Result<Exposure, String>{
+        let tv = ShutterSpeed{sixths: self.tv.sixths - (av.sixths - self.av.sixths)};
+        if !contains(TV_TABLE, tv.sixths){
+            return Err(format!("no shutter speed keeps the exposure at f/{}", av.label()));
+        }
+        Ok(Exposure{iso: self.iso, av, tv})
+    }
+    // shutter priority: set tv and move the aperture so that ev100 stays
+    pub fn with_tv(&self, tv: ShutterSpeed)->Result<Exposure, String>{
+        let av = Aperture{sixths: self.av.sixths - (tv.sixths - self.tv.sixths)};
+        if !contains(AV_TABLE, av.sixths){
+            return Err(format!("no aperture keeps the exposure at {}", tv.label()));
+        }
+        Ok(Exposure{iso: self.iso, av, tv})
+    }
+    // set iso and move the shutter speed so that ev100 stays, the aperture keeps the depth of field
+    pub fn with_iso(&self, iso: Iso)->Result<Exposure, String>{
+        let tv = ShutterSpeed{sixths: self.tv.sixths + (iso.sixths - self.iso.sixths)};
+        if !contains(TV_TABLE, tv.sixths){
+            return Err(format!("no shutter speed keeps the exposure at ISO {}", iso.label()));
+        }
+        Ok(Exposure{iso, av: self.av, tv})
+    }
+}
+
+impl fmt::Display for Exposure{
+    fn fmt(&self, f: &mut fmt::Formatter)->fmt::Result{
+        write!(f, "ISO {} f/{} {}", self.iso.label(), self.av.label(), self.tv.label())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn every_label_parses_back_to_itself(){
+        for (label, _) in ISO_TABLE{
+            assert_eq!(Iso::parse(label).unwrap().label(), *label);
+        }
+        for (label, _) in AV_TABLE{
+            assert_eq!(Aperture::parse(label).unwrap().label(), *label);
+        }
+        for (label, _) in TV_TABLE{
+            assert_eq!(ShutterSpeed::parse(label).unwrap().label(), *label);
+        }
+    }
+
+    #[test]
+    fn other_spellings_of_a_label(){
+        assert_eq!(Aperture::parse("8").unwrap().label(), "8.0");
+        assert_eq!(Aperture::parse("f/5.6").unwrap().label(), "5.6");
+        assert_eq!(ShutterSpeed::parse("1/2").unwrap().label(), "0.5");
+        assert_eq!(ShutterSpeed::parse("15\"").unwrap().label(), "15");
+        assert_eq!(Iso::parse(" 400 ").unwrap().speed(), 400);
+    }
+
+    #[test]
+    fn a_value_between_labels_names_the_nearest(){
+        assert_eq!(Aperture::parse("5.7").unwrap_err(), "5.7 is not an aperture of the table, the nearest is f/5.6");
+        assert_eq!(ShutterSpeed::parse("1/17").unwrap_err(), "1/17 is not a shutter speed of the table, the nearest is 1/15");
+        assert_eq!(Iso::parse("fast").unwrap_err(), "fast is not an iso speed");
+    }
+
+    #[test]
+    fn nominal_labels_are_exact_stops(){
+        assert_eq!(ShutterSpeed::parse("1/15").unwrap().tv(), 4.0);
+        assert_eq!(ShutterSpeed::parse("1/15").unwrap().seconds(), 1.0 / 16.0);
+        assert_eq!(Aperture::parse("6.7").unwrap().av(), 5.5);
+        assert_eq!(Iso::parse("100").unwrap().sv(), 5.0);
+    }
+
+    #[test]
+    fn series_steps(){
+        let full: Vec<&str> = Iso::series(Increment::Full).iter().map(|iso|iso.label()).collect();
+        assert_eq!(&full[..4], &["50", "100", "200", "400"]);
+        // the half stops below f/5.6 share their labels with third stops and are left out
+        let half: Vec<&str> = Aperture::series(Increment::Half).iter().map(|av|av.label()).skip(4).take(6).collect();
+        assert_eq!(half, vec!["4.0", "5.6", "6.7", "8.0", "9.5", "11.0"]);
+        let third: Vec<&str> = ShutterSpeed::series(Increment::Third).iter().map(|tv|tv.label()).take(4).collect();
+        assert_eq!(third, vec!["30", "25", "20", "15"]);
+    }
+
+    #[test]
+    fn ev100_and_the_priority_modes(){
+        let exposure = Exposure::parse("100", "8.0", "1/125").unwrap();
+        assert_eq!(exposure.ev(), 13.0);
+        assert_eq!(exposure.ev100(), 13.0);
+        assert_eq!(Exposure::parse("200", "8.0", "1/125").unwrap().ev100(), 12.0);
+        let opened = exposure.with_av(Aperture::parse("5.6").unwrap()).unwrap();
+        assert_eq!(opened.tv.label(), "1/250");
+        assert_eq!(opened.ev100(), exposure.ev100());
+        let faster = exposure.with_tv(ShutterSpeed::parse("1/500").unwrap()).unwrap();
+        assert_eq!(faster.av.label(), "4.0");
+        let higher = exposure.with_iso(Iso::parse("400").unwrap()).unwrap();
+        assert_eq!(higher.tv.label(), "1/500");
+        assert_eq!(higher.to_string(), "ISO 400 f/8.0 1/500");
+        let fastest = Exposure::parse("100", "8.0", "1/4000").unwrap();
+        assert!(fastest.with_av(Aperture::parse("1.0").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,246 @@
+// messages between the web-view and rust.
+// js sends a Request, rust answers every request with a Reply carrying the same id.
+// everything rust sends, replies and events, goes through dispatch() on the js side.
+use serde::{Deserialize, Serialize};
+use super::calibration;
+use super::capture::Shot;
+use super::config;
+use super::jobs::JobId;
+use super::processing::status;
+
+// bump when a command or a reply changes shape
+pub const PROTOCOL_VERSION: u32 = 2;
+
+#[derive(Deserialize, Debug)]
+pub struct Request{
+    pub id: u64,
+    #[serde(flatten)]
+    pub command: Command,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "command", content = "payload", rename_all = "snake_case")]
+pub enum Command{
+    // first message of a session, nothing else is accepted before it
+    Hello{version: u32},
+    Button,
+    // the page sends it, nothing reads the payload yet
+    #[allow(dead_code)]
+    Menu{name: String, checked: bool},
+    RequestImg,
+    RequestRoot,
+    RequestConnecting,
+    ChangeRoot,
+    RequestExposure,
+    UpdateIso{value: String},
+    UpdateAv{value: String},
+    UpdateTv{value: String},
+    CreateProcess{name: String},
+    SelectProcess{name: String},
+    RenameProcess{name: String, new_name: String},
+    // with_captures also copies the shot frames, the settings are always copied
+    DuplicateProcess{name: String, new_name: String, with_captures: bool},
+    // zip the process into the archive directory of the project and move it to the trash
+    ArchiveProcess{name: String},
+    // move the process to the trash
+    DeleteProcess{name: String},
+    RequestProcesses,
+    RequestProcessing,
+    RequestCaribrations(CaribrationRequest),
+    CancelJob{id: JobId},
+    // the operator did what a job_prompt asked for
+    ConfirmJob{id: JobId},
+    SetLights(config::LightSettings),
+    // shoot into the selected process, focal_length in mm is recorded in state.json, 0 when it is not known
+    CaptureSequence{focal_length: f32},
+    CaptureMissing{focal_length: f32},
+    CaptureShot{shot: Shot, focal_length: f32},
+    // meter the gray card and store the exposure in the selected process
+    AutoExposure,
+    // corners corrected by the operator for the selected process, turns auto trimming off
+    SetTrimPoints{points: [(f32, f32); 4]},
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CaribrationRequest{
+    // inner corners of the checkerboard
+    pub cols: usize,
+    pub rows: usize,
+    pub square_size: f64,
+    pub focal_length_wide: f32,
+    pub focal_length_tele: f32,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode{
+    // not json or not a request object
+    Malformed,
+    UnknownCommand,
+    // the command is known but its payload does not match
+    InvalidPayload,
+    HandshakeRequired,
+    VersionMismatch,
+    // cancel for a job that is not running
+    UnknownJob,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Response{
+    Hello{version: u32},
+    // the command was handled, results arrive through the usual set_* functions
+    Done,
+    // the command goes on in a background job, see the job_* events
+    Started{job: JobId},
+    Error{code: ErrorCode, message: String},
+}
+
+#[derive(Serialize, Debug)]
+pub struct Reply{
+    // None when the id could not be read from the request
+    pub id: Option<u64>,
+    #[serde(flatten)]
+    pub response: Response,
+}
+
+impl Reply{
+    pub fn new(id: u64, response: Response)->Self{
+        Reply{id: Some(id), response}
+    }
+    pub fn error(id: Option<u64>, code: ErrorCode, message: &str)->Self{
+        Reply{id, response: Response::Error{code, message: message.to_string()}}
+    }
+}
+
+// texture map shown in the preview
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureMap{
+    Albedo,
+    Normal,
+    Roughness,
+}
+
+// pushed to the frontend without being asked, or as the result of a command
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", content = "payload", rename_all = "snake_case")]
+pub enum Event{
+    Error{title: String, message: String},
+    Root{path: String},
+    // project.json of the root, the times in rfc 3339. uuid and times are null until project.json is written
+    Project{name: String, uuid: Option<String>, created: Option<String>, modified: Option<String>},
+    Lights(config::LightSettings),
+    // labels as in the exposure tables, ev at ISO 100
+    Exposure{iso: String, av: String, tv: String, ev: f32},
+    // the values the exposure controls offer, those of the connected body when there is one
+    ExposureValues{iso: Vec<String>, av: Vec<String>, tv: Vec<String>},
+    Connection{device: String},
+    // mime is the type of the encoded file, e.g. image/png for the processed maps
+    Image{map: TextureMap, mime: String, base64: String},
+    // full paths, and what each process holds on disk in the same order
+    ProcessList{processes: Vec<String>, statuses: Vec<status::ProcessScan>},
+    // a low confidence asks for a manual correction
+    TrimPoints{points: [(f32, f32); 4], confidence: f32, needs_correction: bool},
+    CaribrationResult(calibration::Report),
+    // which frames of the selected process are shot
+    CaptureState(config::ProcessingState),
+    JobStarted{id: JobId, name: String},
+    // fraction is 0..1 of the whole job
+    JobProgress{id: JobId, stage: String, fraction: f32},
+    JobLog{id: JobId, line: String},
+    // the job waits until the operator answers with confirm_job
+    JobPrompt{id: JobId, message: String},
+    JobFinished{id: JobId},
+    JobFailed{id: JobId, message: String},
+    JobCancelled{id: JobId},
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message{
+    Reply(Reply),
+    Event(Event),
+}
+
+// js that hands a message to the dispatcher.
+// json is a valid js expression except for U+2028 and U+2029,
+// which older engines do not accept inside string literals.
+pub fn dispatch_script(message: &Message)->String{
+    let json = serde_json::to_string(message).unwrap()
+        .replace('\u{2028}', "\\u2028")
+        .replace('\u{2029}', "\\u2029");
+    format!("dispatch({})", json)
+}
+
+// parse a request, a failure comes back as the error reply to send
+pub fn parse_request(arg: &str)->Result<Request, Reply>{
+    let value: serde_json::Value = serde_json::from_str(arg)
+        .map_err(|e|Reply::error(None, ErrorCode::Malformed, &e.to_string()))?;
+    let id = value.get("id").and_then(|id|id.as_u64());
+    let command = value.get("command").and_then(|c|c.as_str()).map(|c|c.to_string());
+    if id.is_none() || command.is_none(){
+        return Err(Reply::error(id, ErrorCode::Malformed, "a request needs a numeric id and a command name"));
+    }
+    serde_json::from_value(value).map_err(|e|{
+        let message = e.to_string();
+        // serde reports an unknown tag as "unknown variant"
+        let code = if message.starts_with("unknown variant") { ErrorCode::UnknownCommand } else { ErrorCode::InvalidPayload };
+        Reply::error(id, code, &format!("{}: {}", command.unwrap(), message))
+    })
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn error_code(reply: &Reply)->ErrorCode{
+        match &reply.response{
+            Response::Error{code, ..}=>*code,
+            response=>panic!("not an error: {:?}", response),
+        }
+    }
+
+    #[test]
+    fn parse_request_reads_commands_and_payloads(){
+        let request = parse_request(r#"{"id": 3, "command": "hello", "payload": {"version": 1}}"#).unwrap();
+        assert_eq!(request.id, 3);
+        assert!(matches!(request.command, Command::Hello{version: 1}));
+        let request = parse_request(r#"{"id": 4, "command": "capture_missing", "payload": {"focal_length": 35}}"#).unwrap();
+        assert!(matches!(request.command, Command::CaptureMissing{focal_length} if focal_length == 35.0));
+        let request = parse_request(r#"{"id": 5, "command": "set_trim_points", "payload": {"points": [[0, 1], [0, 0], [1, 0], [1, 1]]}}"#).unwrap();
+        match request.command{
+            Command::SetTrimPoints{points}=>assert_eq!(points, [(0.0, 1.0), (0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]),
+            command=>panic!("{:?}", command),
+        }
+        let request = parse_request(r#"{"id": 6, "command": "capture_shot", "payload": {"shot": {"take": 2}, "focal_length": 0}}"#).unwrap();
+        assert!(matches!(request.command, Command::CaptureShot{shot: Shot::Take(2), focal_length} if focal_length == 0.0));
+    }
+
+    #[test]
+    fn parse_request_tells_what_is_wrong(){
+        let reply = parse_request("{\"id\": 1,").unwrap_err();
+        assert_eq!((reply.id, error_code(&reply)), (None, ErrorCode::Malformed));
+        let reply = parse_request(r#"{"command": "button"}"#).unwrap_err();
+        assert_eq!((reply.id, error_code(&reply)), (None, ErrorCode::Malformed));
+        let reply = parse_request(r#"{"id": 2}"#).unwrap_err();
+        assert_eq!((reply.id, error_code(&reply)), (Some(2), ErrorCode::Malformed));
+        let reply = parse_request(r#"{"id": 7, "command": "format_disk"}"#).unwrap_err();
+        assert_eq!((reply.id, error_code(&reply)), (Some(7), ErrorCode::UnknownCommand));
+        let reply = parse_request(r#"{"id": 8, "command": "hello", "payload": {"version": "one"}}"#).unwrap_err();
+        assert_eq!((reply.id, error_code(&reply)), (Some(8), ErrorCode::InvalidPayload));
+        let reply = parse_request(r#"{"id": 9, "command": "set_trim_points", "payload": {"points": [[0, 1]]}}"#).unwrap_err();
+        assert_eq!((reply.id, error_code(&reply)), (Some(9), ErrorCode::InvalidPayload));
+        // the focal length is part of the capture commands since protocol 2
+        let reply = parse_request(r#"{"id": 10, "command": "capture_sequence"}"#).unwrap_err();
+        assert_eq!((reply.id, error_code(&reply)), (Some(10), ErrorCode::InvalidPayload));
+    }
+
+    #[test]
+    fn dispatch_script_wraps_the_message(){
+        let script = dispatch_script(&Message::Reply(Reply::new(3, Response::Done)));
+        assert_eq!(script, r#"dispatch({"type":"reply","id":3,"kind":"done"})"#);
+        let script = dispatch_script(&Message::Event(Event::Root{path: "a\u{2028}b".to_string()}));
+        assert_eq!(script, r#"dispatch({"type":"event","event":"root","payload":{"path":"a\u2028b"}})"#);
+    }
+}
@@ -0,0 +1,125 @@
+// long running work on worker threads, so the web-view keeps responding.
+// a job reports progress, log lines and its outcome as events through web_view::Handle::dispatch.
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use super::error::{Error, Result};
+use super::ipc::{self, Event, Message};
+
+pub type JobId = u64;
+
+// how often a job waiting for the operator looks at its cancel flag
+const ASK_POLL: Duration = Duration::from_millis(200);
+
+// shared between a running job and the runner
+struct JobControl{
+    cancelled: AtomicBool,
+    // set by JobRunner::confirm while the job waits in JobContext::ask
+    confirmed: Mutex<bool>,
+    answered: Condvar,
+}
+
+// what a running job gets to talk to the frontend
+pub struct JobContext{
+    id: JobId,
+    control: Arc<JobControl>,
+    sink: Box<dyn Fn(Event) + Send>,
+}
+
+impl JobContext{
+    pub fn send(&self, event: Event){
+        (self.sink)(event);
+    }
+    // fraction is 0..1 of the whole job
+    pub fn progress(&self, stage: &str, fraction: f32){
+        self.send(Event::JobProgress{id: self.id, stage: stage.to_string(), fraction});
+    }
+    pub fn log(&self, line: &str){
+        self.send(Event::JobLog{id: self.id, line: line.to_string()});
+    }
+    pub fn is_cancelled(&self)->bool{
+        self.control.cancelled.load(Ordering::SeqCst)
+    }
+    // error once the job was cancelled, call between steps and return early with ?
+    pub fn checkpoint(&self)->Result<()>{
+        if self.is_cancelled(){
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+    // show message to the operator and wait until it is confirmed, or the job is cancelled
+    pub fn ask(&self, message: &str)->Result<()>{
+        *self.control.confirmed.lock().unwrap() = false;
+        self.send(Event::JobPrompt{id: self.id, message: message.to_string()});
+        let mut confirmed = self.control.confirmed.lock().unwrap();
+        while !*confirmed{
+            self.checkpoint()?;
+            confirmed = self.control.answered.wait_timeout(confirmed, ASK_POLL).unwrap().0;
+        }
+        Ok(())
+    }
+}
+
+pub struct JobRunner{
+    next_id: JobId,
+    // the jobs that have not finished yet
+    running: Arc<Mutex<HashMap<JobId, Arc<JobControl>>>>,
+}
+
+impl JobRunner{
+    pub fn new()->Self{
+        JobRunner{next_id: 1, running: Arc::new(Mutex::new(HashMap::new()))}
+    }
+    // run job on its own thread. the job returns an error to fail,
+    // an error after a cancel is reported as cancelled instead.
+    pub fn spawn<T: 'static, F>(&mut self, handle: web_view::Handle<T>, name: &str, job: F)->JobId
+        where F: FnOnce(&JobContext)->Result<()> + Send + 'static
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+        let control = Arc::new(JobControl{cancelled: AtomicBool::new(false), confirmed: Mutex::new(false), answered: Condvar::new()});
+        self.running.lock().unwrap().insert(id, control.clone());
+        let sink = move |event: Event|{
+            let script = ipc::dispatch_script(&Message::Event(event));
+            let _ = handle.dispatch(move |webview|webview.eval(&script));
+        };
+        let context = JobContext{id, control, sink: Box::new(sink)};
+        context.send(Event::JobStarted{id, name: name.to_string()});
+        let running = self.running.clone();
+        std::thread::spawn(move ||{
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(||job(&context)))
+                .unwrap_or_else(|_|Err(Error::processing("run the job", "the job panicked")));
+            running.lock().unwrap().remove(&id);
+            let event = match result{
+                Ok(())=>Event::JobFinished{id},
+                Err(_) if context.is_cancelled()=>Event::JobCancelled{id},
+                Err(error)=>Event::JobFailed{id, message: error.to_string()},
+            };
+            context.send(event);
+        });
+        id
+    }
+    // ask a job to stop at its next checkpoint, false when it is not running
+    pub fn cancel(&self, id: JobId)->bool{
+        match self.running.lock().unwrap().get(&id){
+            Some(control)=>{
+                control.cancelled.store(true, Ordering::SeqCst);
+                control.answered.notify_all();
+                true
+            }
+            None=>false,
+        }
+    }
+    // answer the prompt of a job, false when it is not running
+    pub fn confirm(&self, id: JobId)->bool{
+        match self.running.lock().unwrap().get(&id){
+            Some(control)=>{
+                *control.confirmed.lock().unwrap() = true;
+                control.answered.notify_all();
+                true
+            }
+            None=>false,
+        }
+    }
+}
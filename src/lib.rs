@@ -1,202 +1,660 @@
-use std::collections::HashMap;
-use std::path::{/*Path, */PathBuf};
-use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 mod config;
 use config::{ProjectSettings};
+use config::manifest::{self, ProcessStatus};
 mod camera;
-use edsdk::wrap;
-//use edsdk::types;
-
-#[derive(Serialize, Deserialize, Debug)]
-struct RecieveInfo{
-    id: i32,
-    name: String,
-    value: String,
-    info: HashMap<String, String>
-}
+use camera::SharedCamera;
+mod processing;
+mod exposure;
+mod calibration;
+mod capture;
+use capture::Shot;
+pub mod cli;
+mod ipc;
+use ipc::{Command, Event, Message, Reply, Response, ErrorCode, TextureMap};
+mod jobs;
+use jobs::{JobId, JobRunner};
+pub mod error;
+pub use error::Error;
+use error::Result;
+#[cfg(test)]
+mod testing;
 
 pub struct Application{
     project: ProjectSettings,
-    edsdk: wrap::Library,
-    camera_device: Option<wrap::Camera>,
-    camera_session: Option<wrap::Session>,
+    camera: SharedCamera,
+    jobs: JobRunner,
+    // set once the frontend has said hello with a matching protocol version
+    handshaken: bool,
+    // errors from before the ui was there, shown after the hello
+    startup_errors: Vec<Error>,
 }
 
+impl Default for Application{
+    fn default()->Self{
+        Application::new()
+    }
+}
 
 impl Application{
+    // a broken texshooter.json or a missing camera sdk is reported in the ui,
+    // the app starts with the default settings or without a camera instead
     pub fn new()->Self{
-        let edsdk = wrap::Library::initialize();
-        return Application{project: ProjectSettings::load().unwrap(), edsdk: edsdk.unwrap(), camera_device: None, camera_session: None};
+        let mut startup_errors = Vec::new();
+        let project = ProjectSettings::load().unwrap_or_else(|error|{
+            startup_errors.push(error);
+            ProjectSettings::new()
+        });
+        let camera = camera::create_backend().unwrap_or_else(|error|{
+            let reason = error.message();
+            startup_errors.push(error);
+            Box::new(camera::UnavailableBackend::new(&reason))
+        });
+        Application{
+            project,
+            camera: std::sync::Arc::new(std::sync::Mutex::new(camera)),
+            jobs: JobRunner::new(),
+            handshaken: false,
+            startup_errors
+        }
+    }
+    // the only way to talk to the webview, payloads always travel as json
+    pub fn send_event<T>(&self, webview: &mut web_view::WebView<T>, event: Event){
+        let _ = webview.eval(&ipc::dispatch_script(&Message::Event(event)));
     }
     // send error
     pub fn send_error<T>(&self, webview: &mut web_view::WebView<T>, title: &str, message: &str){
-        let _ = webview.eval(&format!("error_msg(\"{}\", \"{}\")", title, message));
+        self.send_event(webview, Event::Error{title: title.to_string(), message: message.to_string()});
+    }
+    // send error with the title and message of error
+    pub fn report_error<T>(&self, webview: &mut web_view::WebView<T>, error: &Error){
+        self.send_error(webview, &error.title(), &error.message());
     }
     // send project data to webview
     pub fn send_project_root<T>(&self, webview: &mut web_view::WebView<T>){
-        let root_path = str::replace(self.project.get_root_path(), "\\", "\\\\");
-        let _ = webview.eval(&format!("set_root(\"{}\")", root_path));
+        self.send_event(webview, Event::Root{path: self.project.get_root_path().to_string()});
+        match self.project.read_manifest(){
+            Ok(Some(manifest))=>self.send_event(webview, Event::Project{
+                name: manifest.get_name().to_string(),
+                uuid: Some(manifest.get_uuid().to_string()),
+                created: Some(manifest.get_created().to_rfc3339()),
+                modified: Some(manifest.get_modified().to_rfc3339()),
+            }),
+            // project.json is written with the first change to the project
+            Ok(None)=>self.send_event(webview, Event::Project{
+                name: Path::new(self.project.get_root_path()).file_name().map(|name|name.to_string_lossy().into_owned()).unwrap_or_default(),
+                uuid: None,
+                created: None,
+                modified: None,
+            }),
+            Err(error)=>self.report_error(webview, &error),
+        }
+        self.send_event(webview, Event::Lights(self.project.get_lights().clone()));
     }
     // change projects root path
     pub fn change_project_root<T>(&mut self, webview: &mut web_view::WebView<T>){
         // TODO:have to use current root path
-        let mut current_path = std::env::current_exe().unwrap();
-        current_path.pop();
+        let current_path = executable_dir().unwrap_or_default();
         let result = web_view::DialogBuilder::new(webview).choose_directory("select a project root directory", current_path);
-        if result.is_ok(){
-            let path = result.unwrap();
-            if path.is_some(){
-                let path = path.unwrap();
-                self.project.set_root_path(path.to_str().unwrap());
-                self.project.save();
-                self.send_project_root(webview);
+        if let Ok(Some(path)) = result{
+            self.project.set_root_path(&path.to_string_lossy());
+            if let Err(error) = self.project.save(){
+                self.report_error(webview, &error);
             }
+            self.send_project_root(webview);
+            // the session was opened for the processes of the old root
+            self.disconnect_camera(webview);
         }
     }
     // send image
-    pub fn send_image<T>(&self, webview: &mut web_view::WebView<T>, image_name: &str, func_name: &str)
+    pub fn send_image<T>(&self, webview: &mut web_view::WebView<T>, image_name: &str, map: TextureMap)
     {
-        let mut path = std::env::current_exe().unwrap();
-        path.pop();
-        path.push(image_name);
-        let jpg = std::fs::read(&path).unwrap();
-        let _ = webview.eval(&format!("{}(\"{}\")", func_name, base64::encode(&jpg)));
+        let img = executable_dir().and_then(|mut path|{
+            path.push(image_name);
+            std::fs::read(&path).map_err(|e|Error::io(&path, "load the preview image", e))
+        });
+        match img{
+            Ok(img)=>self.send_event(webview, Event::Image{map, mime: image_mime(Path::new(image_name)).to_string(), base64: base64::encode(&img)}),
+            Err(error)=>self.report_error(webview, &error),
+        }
     }
     pub fn send_process_list<T>(&self, webview: &mut web_view::WebView<T>){
-        let pathes = self.project.calc_process_list();
-        let pathes: Vec<String> = pathes.iter().map(|p|format!("\"{}\"",p)).collect();
-        let path_string = pathes.join(",");
-        let _ = webview.eval(&format!("set_process_list(\"[{}]\")", path_string));
+        match self.project.calc_process_list(){
+            Ok(pathes)=>{
+                let statuses = pathes.iter().map(|path|processing::status::scan(Path::new(path))).collect();
+                self.send_event(webview, Event::ProcessList{processes: pathes, statuses});
+            }
+            Err(error)=>self.report_error(webview, &error),
+        }
     }
-    // receive iso changed
-    pub fn receive_iso(&mut self, iso_speed: &str){
-        self.project.set_iso(iso_speed);
-        if self.camera_session.is_some(){
-            let _ = self.camera_session.as_ref().map(|session|{
-                session.set_iso_speed(camera::convert_iso(self.project.get_iso()))
-            });
+    // send an exposure and its ev to the ui
+    pub fn send_exposure<T>(&self, webview: &mut web_view::WebView<T>, settings: &config::ExposureSettings){
+        match settings.to_exposure(){
+            Ok(exposure)=>{
+                self.send_event(webview, exposure_event(&exposure));
+            }
+            Err(error)=>{
+                self.report_error(webview, &error);
+            }
         }
     }
-    // receive av changed
-    pub fn receive_av(&mut self, aperture_value: &str){
-        self.project.set_aperture_value(aperture_value);
-        if self.camera_session.is_some(){
-            let _ = self.camera_session.as_ref().map(|session|{
-                session.set_av(camera::convert_av(self.project.get_aperture_value_as_str()))
-            });
+    // the choices of the exposure controls. the values of the body that the exposure tables know,
+    // every third stop when no camera is connected.
+    pub fn send_exposure_values<T>(&self, webview: &mut web_view::WebView<T>){
+        let camera = self.camera.lock().unwrap();
+        let supported = if camera.is_session_open(){
+            let iso = camera.supported_iso().map(|values|values.iter().map(|iso|iso.to_string()).collect());
+            match (iso, camera.supported_av(), camera.supported_tv()){
+                (Ok(iso), Ok(av), Ok(tv))=>Some((iso, av, tv)),
+                (Err(error), _, _) | (_, Err(error), _) | (_, _, Err(error))=>{
+                    self.report_error(webview, &error);
+                    None
+                }
+            }
         }
+        else{
+            None
+        };
+        let event = match supported{
+            Some((iso, av, tv))=>Event::ExposureValues{
+                iso: known_labels(iso, |value|exposure::Iso::parse(value).map(|iso|iso.label())),
+                av: known_labels(av, |value|exposure::Aperture::parse(value).map(|av|av.label())),
+                tv: known_labels(tv, |value|exposure::ShutterSpeed::parse(value).map(|tv|tv.label())),
+            },
+            None=>Event::ExposureValues{
+                iso: exposure::Iso::series(exposure::Increment::Third).iter().map(|iso|iso.label().to_string()).collect(),
+                av: exposure::Aperture::series(exposure::Increment::Third).iter().map(|av|av.label().to_string()).collect(),
+                tv: exposure::ShutterSpeed::series(exposure::Increment::Third).iter().map(|tv|tv.label().to_string()).collect(),
+            },
+        };
+        self.send_event(webview, event);
     }
-    // receive tv changed
-    pub fn receive_tv(&mut self, time_value: &str){
-        self.project.set_time_value(time_value);
-        if self.camera_session.is_some(){
-            let _ = self.camera_session.as_ref().map(|session|{
-                session.set_tv(camera::convert_tv(self.project.get_time_value_as_str()))
-            });
+    // the exposure of the selected process, the last one of the project when none is selected
+    fn current_exposure(&self)->config::ExposureSettings{
+        let process_name = self.project.get_last_processing();
+        if !process_name.is_empty(){
+            if let Ok(settings) = self.project.load_process(process_name){
+                return settings.get_exposure().clone();
+            }
+        }
+        self.project.get_last_exposure().clone()
+    }
+    // store exposure in process.json of the selected process and as the last one of the project
+    fn store_exposure(&mut self, exposure: &exposure::Exposure)->Result<()>{
+        let settings = config::ExposureSettings::from_exposure(exposure);
+        self.project.set_last_exposure(settings.clone());
+        self.project.save()?;
+        let process_name = self.project.get_last_processing().to_string();
+        if process_name.is_empty(){
+            return Ok(());
         }
+        let mut process = self.project.load_process(&process_name)?;
+        process.set_exposure(settings);
+        process.save(&self.project.get_process_path(&process_name).join(config::PROCESS_SETTING_FILE_NAME))
+    }
+    // set exposure on the open camera session
+    fn set_camera_exposure(&self, exposure: &exposure::Exposure)->Result<()>{
+        let mut camera = self.camera.lock().unwrap();
+        if camera.is_session_open(){
+            let error = |error: Error|Error::camera(&format!("set {} on the camera", exposure), &error.message());
+            camera.set_iso(exposure.iso.speed()).map_err(error)?;
+            camera.set_av(exposure.av.label()).map_err(error)?;
+            camera.set_tv(exposure.tv.label()).map_err(error)?;
+        }
+        Ok(())
+    }
+    // set the current exposure on the open camera session
+    fn push_exposure(&self)->Result<()>{
+        self.set_camera_exposure(&self.current_exposure().to_exposure()?)
+    }
+    // change one value of the current exposure, the camera has to accept it before it is stored
+    fn change_exposure<F>(&mut self, change: F)->Result<()>
+        where F: FnOnce(exposure::Exposure)->exposure::Exposure
+    {
+        // a broken exposure in the settings is replaced instead of blocking every change
+        let current = self.current_exposure().to_exposure().or_else(|_|config::ExposureSettings::new().to_exposure())?;
+        let exposure = change(current);
+        self.set_camera_exposure(&exposure)?;
+        self.store_exposure(&exposure)
+    }
+    // receive iso changed
+    pub fn receive_iso(&mut self, iso_speed: &str)->Result<()>{
+        let iso = exposure::Iso::parse(iso_speed).map_err(|message|Error::validation("set the iso", &message))?;
+        self.change_exposure(|exposure|exposure::Exposure{iso, ..exposure})
+    }
+    // receive av changed
+    pub fn receive_av(&mut self, aperture_value: &str)->Result<()>{
+        let av = exposure::Aperture::parse(aperture_value).map_err(|message|Error::validation("set the aperture", &message))?;
+        self.change_exposure(|exposure|exposure::Exposure{av, ..exposure})
+    }
+    // receive tv changed
+    pub fn receive_tv(&mut self, time_value: &str)->Result<()>{
+        let tv = exposure::ShutterSpeed::parse(time_value).map_err(|message|Error::validation("set the shutter speed", &message))?;
+        self.change_exposure(|exposure|exposure::Exposure{tv, ..exposure})
     }
     // connect and open session
     pub fn connect_camera<T>(&mut self, webview: &mut web_view::WebView<T>){
-        let mut devices = self.edsdk.get_device_list();
-        if devices.len() > 0{
-            self.camera_device = std::mem::replace(&mut devices[0], None);
-            let session = self.camera_device.as_ref().map(|dev|{
-                let info = dev.get_device_info().unwrap();
-                let _ = webview.eval(&format!("set_connection(\"{}\")", info.description));
-                let fmt = format!("set_connection(\"{}\")", info.description);
-                let _ = webview.eval(&fmt);
-                let session = dev.open_session();
-                if session.is_ok(){
-                    // TODO: set values from config
-                    Some(session.unwrap())
+        let mut camera = self.camera.lock().unwrap();
+        let devices = camera.list_devices();
+        if !devices.is_empty(){
+            match camera.open_session(0){
+                Ok(device)=>{
+                    self.send_event(webview, Event::Connection{device});
+                    drop(camera);
+                    self.send_exposure_values(webview);
+                    if let Err(error) = self.push_exposure(){
+                        self.report_error(webview, &error);
+                    }
+                    self.send_exposure(webview, &self.current_exposure());
                 }
-                else{
-                    None
+                Err(error)=>{
+                    self.send_event(webview, Event::Connection{device: devices[0].clone()});
+                    self.report_error(webview, &error);
                 }
-            });
-            self.camera_session = session.unwrap();
+            }
         }
         else{
-            let _ = webview.eval(&format!("set_connection(\"disconnecting\")"));
+            drop(camera);
+            self.disconnect_camera(webview);
         }
     }
+    // close the session, the exposure controls fall back to every third stop
+    fn disconnect_camera<T>(&mut self, webview: &mut web_view::WebView<T>){
+        self.camera.lock().unwrap().close_session();
+        self.send_event(webview, Event::Connection{device: "disconnecting".to_string()});
+        self.send_exposure_values(webview);
+    }
     pub fn create_process<T>(&mut self, webview: &mut web_view::WebView<T>, process_name: &str){
-        let result = self.project.create_process(process_name);
-        if result.is_some(){
-            self.select_process(webview, process_name);
+        match self.project.create_process(process_name){
+            Ok(_)=>self.select_process(webview, process_name),
+            Err(error)=>self.report_error(webview, &error),
         }
-        else{
-            self.send_error(webview, "failed to create process", &format!("couldn't make a dir {} or a setting file on that.", process_name));
+    }
+    pub fn rename_process<T>(&mut self, webview: &mut web_view::WebView<T>, process_name: &str, new_name: &str){
+        let result = self.project.rename_process(process_name, new_name)
+            // the selection follows the new name
+            .and_then(|_|self.project.save());
+        self.process_changed(webview, result);
+    }
+    pub fn duplicate_process<T>(&mut self, webview: &mut web_view::WebView<T>, process_name: &str, new_name: &str, with_captures: bool){
+        let result = self.project.duplicate_process(process_name, new_name, with_captures);
+        self.process_changed(webview, result);
+    }
+    pub fn archive_process<T>(&mut self, webview: &mut web_view::WebView<T>, process_name: &str){
+        let result = self.project.archive_process(process_name)
+            .and_then(|_|self.project.save());
+        self.process_changed(webview, result);
+    }
+    pub fn delete_process<T>(&mut self, webview: &mut web_view::WebView<T>, process_name: &str){
+        let result = self.project.delete_process(process_name)
+            .and_then(|_|self.project.save());
+        self.process_changed(webview, result);
+    }
+    // the ui lists the processes again after a rename, a copy or a removal
+    fn process_changed<T>(&self, webview: &mut web_view::WebView<T>, result: Result<()>){
+        if let Err(error) = result{
+            self.report_error(webview, &error);
         }
+        self.send_process_list(webview);
     }
     pub fn select_process<T>(&mut self, webview: &mut web_view::WebView<T>, process_name: &str){
-        if self.project.set_last_processing(process_name){
-            ;
+        if let Err(error) = self.project.set_last_processing(process_name){
+            self.report_error(webview, &error);
+            return;
         }
-        else{
-            self.send_error(webview, "failed to select process", &format!("process {} may not be valid.", process_name));
+        let state = capture::load_state(&self.project.get_process_path(process_name));
+        self.send_event(webview, Event::CaptureState(state));
+        // shoot again with the exposure the process was shot with
+        if let Err(error) = self.push_exposure(){
+            self.report_error(webview, &error);
         }
+        self.send_exposure(webview, &self.current_exposure());
     }
-
-    pub fn invoked<T>(&mut self, webview: &mut web_view::WebView<T>, arg: &str){
-        let deserialized : RecieveInfo = serde_json::from_str(arg).unwrap();
-        match deserialized.name.as_str(){
-            "button"=>{
-                eprint!("pressed")
-            }
-            "menu"=>{
-                let checked = deserialized.info.get("checked");
-                if checked.is_some(){
-                    match &*(checked.unwrap().as_str()){
-                        "true"=>{
-                            eprint!("menu {:?}", deserialized.value)
-                        },
-                        _=>{
-                        }
-                    }
+    // store corners corrected by the operator in the selected process.
+    // auto trimming is turned off, otherwise the next processing would detect them again.
+    fn store_trim_points(&self, points: [(f32, f32); 4])->Result<()>{
+        let context = "set the trim points";
+        if points.iter().any(|(x, y)|!(0.0..=1.0).contains(x) || !(0.0..=1.0).contains(y)){
+            return Err(Error::validation(context, "the corners have to lie in the frame, between 0 and 1"));
+        }
+        let process_name = self.project.get_last_processing();
+        if process_name.is_empty(){
+            return Err(Error::validation(context, "no process is selected"));
+        }
+        let mut settings = self.project.load_process(process_name)?;
+        settings.get_options_mut().set_trim_points(points);
+        settings.get_options_mut().set_auto_trimming(false);
+        settings.save(&self.project.get_process_path(process_name).join(config::PROCESS_SETTING_FILE_NAME))
+    }
+    // build texture maps of the selected process on a worker thread
+    pub fn run_processing<T: 'static>(&mut self, webview: &mut web_view::WebView<T>)->Option<JobId>{
+        let process_name = self.project.get_last_processing().to_string();
+        if process_name.is_empty(){
+            self.send_error(webview, "failed to process", "no process is selected.");
+            return None;
+        }
+        let mut settings = match self.project.load_process(&process_name){
+            Ok(settings)=>settings,
+            Err(error)=>{
+                self.report_error(webview, &error);
+                return None;
+            }
+        };
+        let process_dir = self.project.get_process_path(&process_name);
+        let root = PathBuf::from(self.project.get_root_path());
+        let job = self.jobs.spawn(webview.handle(), &format!("processing {}", process_name), move |job|{
+            let report = processing::process(&process_dir, &mut settings, &mut |stage, fraction|{
+                job.checkpoint()?;
+                job.progress(stage, fraction);
+                Ok(())
+            })?;
+            manifest::update_process_status(&root, &process_name, ProcessStatus::Processed)?;
+            for warning in &report.warnings{
+                job.log(warning);
+            }
+            for stage in &report.reused{
+                job.log(&format!("reused the cached {}", stage.name()));
+            }
+            for output in &report.outputs{
+                job.log(&format!("wrote {}", output.display()));
+            }
+            if let Some(trim) = report.trim{
+                job.send(trim_points_event(&trim));
+            }
+            let maps = [
+                (processing::ALBEDO_FILE_NAME, TextureMap::Albedo),
+                (processing::NORMAL_FILE_NAME, TextureMap::Normal),
+                (processing::ROUGHNESS_FILE_NAME, TextureMap::Roughness),
+            ];
+            for (file_name, map) in maps.iter(){
+                if let Some(event) = image_event(&process_dir.join(file_name), *map){
+                    job.send(event);
                 }
             }
-            "request_img"=>{
-                self.send_image(webview, "rust_albedo.jpg", "set_albedo");
-                self.send_image(webview, "rust_normal.jpg", "set_normal");
-                self.send_image(webview, "rust_roughness.jpg", "set_roughness");
+            Ok(())
+        });
+        Some(job)
+    }
+
+    // shoot frames into the selected process on a worker thread, None shoots the missing ones
+    pub fn run_capture<T: 'static>(&mut self, webview: &mut web_view::WebView<T>, shots: Option<Vec<Shot>>, focal_length: f32)->Option<JobId>{
+        let process_name = self.project.get_last_processing().to_string();
+        if process_name.is_empty(){
+            self.send_error(webview, "failed to capture", "no process is selected.");
+            return None;
+        }
+        let process_dir = self.project.get_process_path(&process_name);
+        let shots = shots.unwrap_or_else(||Shot::missing(&capture::load_state(&process_dir)));
+        let camera = self.camera.clone();
+        let light_settings = self.project.get_lights().clone();
+        let root = PathBuf::from(self.project.get_root_path());
+        let job = self.jobs.spawn(webview.handle(), &format!("capturing {}", process_name), move |job|{
+            let count = shots.len();
+            if count == 0{
+                job.log("every frame is already shot");
+                return Ok(());
+            }
+            let mut lights = capture::light::create_controller(&light_settings, |message|job.ask(message))?;
+            let state = capture::shoot(&camera, lights.as_mut(), &process_dir, &shots, focal_length, &mut |shot, i|{
+                job.checkpoint()?;
+                job.progress(&format!("shooting {}", shot.file_name()), i as f32 / count as f32);
+                Ok(())
+            });
+            // report what was shot even when the sequence stopped half way
+            let shot = capture::load_state(&process_dir);
+            manifest::update_process_status(&root, &process_name, capture::capture_status(&shot))?;
+            job.send(Event::CaptureState(shot));
+            state.map(|_|())
+        });
+        Some(job)
+    }
+    // meter the gray card of the selected process on a worker thread, iso and aperture of the process are kept
+    pub fn run_auto_exposure<T: 'static>(&mut self, webview: &mut web_view::WebView<T>)->Option<JobId>{
+        let process_name = self.project.get_last_processing().to_string();
+        if process_name.is_empty(){
+            self.send_error(webview, "failed to meter the gray card", "no process is selected.");
+            return None;
+        }
+        let start = self.project.load_process(&process_name).and_then(|settings|Ok((settings.get_exposure().to_exposure()?, settings)));
+        let (start, settings) = match start{
+            Ok(start)=>start,
+            Err(error)=>{
+                self.report_error(webview, &error);
+                return None;
+            }
+        };
+        let process_dir = self.project.get_process_path(&process_name);
+        let camera = self.camera.clone();
+        let light_settings = self.project.get_lights().clone();
+        let job = self.jobs.spawn(webview.handle(), &format!("metering {}", process_name), move |job|{
+            let mut settings = settings;
+            let mut lights = capture::light::create_controller(&light_settings, |message|job.ask(message))?;
+            job.progress("metering the gray card", 0.0);
+            let exposure = capture::metering::auto_expose(&camera, lights.as_mut(), &process_dir, &start, &mut |exposure, metering|{
+                job.log(&format!("{}: card {:.3}, {:.2}% clipped", exposure, metering.luminance, metering.clipped * 100.0));
+                job.checkpoint()
+            })?;
+            settings.set_exposure(config::ExposureSettings::from_exposure(&exposure));
+            settings.save(&process_dir.join(config::PROCESS_SETTING_FILE_NAME))?;
+            job.log(&format!("the gray card is exposed at {}", exposure));
+            job.send(exposure_event(&exposure));
+            Ok(())
+        });
+        Some(job)
+    }
+
+    // calibrate the lens from the checkerboard shots in the project on a worker thread
+    pub fn request_caribrations<T: 'static>(&mut self, webview: &mut web_view::WebView<T>, request: &ipc::CaribrationRequest)->JobId{
+        let pattern = calibration::Pattern{cols: request.cols, rows: request.rows, square_size: request.square_size};
+        let (focal_length_wide, focal_length_tele) = (request.focal_length_wide, request.focal_length_tele);
+        let root = PathBuf::from(self.project.get_root_path());
+        self.jobs.spawn(webview.handle(), "lens calibration", move |job|{
+            job.progress("calibrating", 0.0);
+            let report = calibration::calibrate_project(&root, &pattern, focal_length_wide, focal_length_tele)?;
+            for rejected in report.wide.rejected.iter().chain(report.tele.iter().flat_map(|tele|tele.rejected.iter())){
+                job.log(&format!("no checkerboard found in {}", rejected));
+            }
+            job.send(Event::CaribrationResult(report));
+            Ok(())
+        })
+    }
+
+    // answer a request, the id lets the frontend match it
+    pub fn send_reply<T>(&self, webview: &mut web_view::WebView<T>, reply: Reply){
+        let _ = webview.eval(&ipc::dispatch_script(&Message::Reply(reply)));
+    }
+    fn handshake(&mut self, version: u32)->Response{
+        self.handshaken = version == ipc::PROTOCOL_VERSION;
+        if self.handshaken{
+            Response::Hello{version: ipc::PROTOCOL_VERSION}
+        }
+        else{
+            Response::Error{
+                code: ErrorCode::VersionMismatch,
+                message: format!("frontend speaks protocol {}, backend speaks {}", version, ipc::PROTOCOL_VERSION)
+            }
+        }
+    }
+    // the ui shows the stored exposure again, also after a rejected value
+    fn exposure_updated<T>(&mut self, webview: &mut web_view::WebView<T>, result: Result<()>){
+        if let Err(error) = result{
+            self.report_error(webview, &error);
+        }
+        self.send_exposure(webview, &self.current_exposure());
+    }
+    fn execute<T: 'static>(&mut self, webview: &mut web_view::WebView<T>, command: Command)->Response{
+        match command{
+            // the button and the menu of the page do nothing on this side yet
+            Command::Hello{..} | Command::Button | Command::Menu{..}=>{
             }
-            "request_root"=>{
+            Command::RequestImg=>{
+                self.send_image(webview, "rust_albedo.jpg", TextureMap::Albedo);
+                self.send_image(webview, "rust_normal.jpg", TextureMap::Normal);
+                self.send_image(webview, "rust_roughness.jpg", TextureMap::Roughness);
+            }
+            Command::RequestRoot=>{
                 self.send_project_root(webview);
             }
-            "request_connecting"=>{
+            Command::RequestConnecting=>{
                 self.connect_camera(webview);
             }
-            "change_root"=>{
+            Command::ChangeRoot=>{
                 self.change_project_root(webview);
             }
-            "update_iso"=>{
-                self.receive_iso(deserialized.value.as_str());
+            Command::RequestExposure=>{
+                self.send_exposure_values(webview);
+                self.send_exposure(webview, &self.current_exposure());
+            }
+            Command::UpdateIso{value}=>{
+                let result = self.receive_iso(&value);
+                self.exposure_updated(webview, result);
             }
-            "update_av"=>{
-                self.receive_av(deserialized.value.as_str());
+            Command::UpdateAv{value}=>{
+                let result = self.receive_av(&value);
+                self.exposure_updated(webview, result);
             }
-            "update_tv"=>{
-                self.receive_tv(deserialized.value.as_str());
+            Command::UpdateTv{value}=>{
+                let result = self.receive_tv(&value);
+                self.exposure_updated(webview, result);
             }
-            "create_process"=>{
-                self.create_process(webview, deserialized.value.as_str());
+            Command::CreateProcess{name}=>{
+                self.create_process(webview, &name);
             }
-            "select_process"=>{
-                self.select_process(webview, deserialized.value.as_str());
+            Command::SelectProcess{name}=>{
+                self.select_process(webview, &name);
             }
-            "request_processes"=>{
+            Command::RenameProcess{name, new_name}=>{
+                self.rename_process(webview, &name, &new_name);
+            }
+            Command::DuplicateProcess{name, new_name, with_captures}=>{
+                self.duplicate_process(webview, &name, &new_name, with_captures);
+            }
+            Command::ArchiveProcess{name}=>{
+                self.archive_process(webview, &name);
+            }
+            Command::DeleteProcess{name}=>{
+                self.delete_process(webview, &name);
+            }
+            Command::RequestProcesses=>{
                 self.send_process_list(webview);
             }
-            "request_caribrations"=>{
-                
+            Command::RequestProcessing=>{
+                if let Some(job) = self.run_processing(webview){
+                    return Response::Started{job};
+                }
+            }
+            Command::RequestCaribrations(request)=>{
+                return Response::Started{job: self.request_caribrations(webview, &request)};
+            }
+            Command::SetLights(lights)=>{
+                self.project.set_lights(lights);
+                if let Err(error) = self.project.save(){
+                    self.report_error(webview, &error);
+                }
+            }
+            Command::CaptureSequence{focal_length}=>{
+                if let Some(job) = self.run_capture(webview, Some(Shot::all()), focal_length){
+                    return Response::Started{job};
+                }
+            }
+            Command::CaptureMissing{focal_length}=>{
+                if let Some(job) = self.run_capture(webview, None, focal_length){
+                    return Response::Started{job};
+                }
+            }
+            Command::CaptureShot{shot, focal_length}=>{
+                if let Some(job) = self.run_capture(webview, Some(vec![shot]), focal_length){
+                    return Response::Started{job};
+                }
+            }
+            Command::AutoExposure=>{
+                if let Some(job) = self.run_auto_exposure(webview){
+                    return Response::Started{job};
+                }
+            }
+            Command::SetTrimPoints{points}=>{
+                if let Err(error) = self.store_trim_points(points){
+                    self.report_error(webview, &error);
+                }
+            }
+            Command::CancelJob{id}=>{
+                if !self.jobs.cancel(id){
+                    return Response::Error{code: ErrorCode::UnknownJob, message: format!("job {} is not running", id)};
+                }
+            }
+            Command::ConfirmJob{id}=>{
+                if !self.jobs.confirm(id){
+                    return Response::Error{code: ErrorCode::UnknownJob, message: format!("job {} is not running", id)};
+                }
             }
-            _=>{
-                eprint!("unknown {:?}", arg)
+        }
+        Response::Done
+    }
+
+    pub fn invoked<T: 'static>(&mut self, webview: &mut web_view::WebView<T>, arg: &str){
+        let request = match ipc::parse_request(arg){
+            Ok(request)=>request,
+            Err(reply)=>{
+                self.send_reply(webview, reply);
+                return;
+            }
+        };
+        let response = match request.command{
+            Command::Hello{version}=>self.handshake(version),
+            _ if !self.handshaken=>Response::Error{
+                code: ErrorCode::HandshakeRequired,
+                message: "send hello before any other command".to_string()
+            },
+            command=>self.execute(webview, command),
+        };
+        let greeted = matches!(response, Response::Hello{..});
+        self.send_reply(webview, Reply::new(request.id, response));
+        if greeted{
+            for error in std::mem::take(&mut self.startup_errors){
+                self.report_error(webview, &error);
             }
         }
     }
 }
 
+// the directory of the executable, the preview images lie next to it
+fn executable_dir()->Result<PathBuf>{
+    let mut path = std::env::current_exe().map_err(|e|Error::io(Path::new("tex_shooter"), "find the executable", e))?;
+    path.pop();
+    Ok(path)
+}
 
+fn exposure_event(exposure: &exposure::Exposure)->Event{
+    Event::Exposure{
+        iso: exposure.iso.label().to_string(),
+        av: exposure.av.label().to_string(),
+        tv: exposure.tv.label().to_string(),
+        ev: exposure.ev100()
+    }
+}
 
+fn image_event(path: &std::path::Path, map: TextureMap)->Option<Event>{
+    let img = std::fs::read(path).ok()?;
+    Some(Event::Image{map, mime: image_mime(path).to_string(), base64: base64::encode(&img)})
+}
+
+// the type for the data uri of an image file, the processed maps are png and the previews jpeg
+fn image_mime(path: &std::path::Path)->&'static str{
+    match path.extension().and_then(|ext|ext.to_str()).map(|ext|ext.to_ascii_lowercase()).as_deref(){
+        Some("png")=>"image/png",
+        _=>"image/jpeg",
+    }
+}
+
+// a low confidence asks for a manual correction
+fn trim_points_event(detection: &processing::boundary::Detection)->Event{
+    let needs_correction = detection.confidence < processing::boundary::MIN_CONFIDENCE;
+    Event::TrimPoints{points: detection.corners, confidence: detection.confidence, needs_correction}
+}
+
+// the values that are labels of the exposure tables, a body in half stops also offers others
+fn known_labels<F>(values: Vec<String>, label: F)->Vec<String>
+    where F: Fn(&str)->std::result::Result<&'static str, String>
+{
+    values.into_iter().filter(|value|label(value).map(|known|known == value).unwrap_or(false)).collect()
+}
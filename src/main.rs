@@ -1,8 +1,12 @@
-use tex_shooter::Application;
+use tex_shooter::{cli, Application};
 
-static HTML_STR: &'static str = include_str!("../html/main.html");
+static HTML_STR: &str = include_str!("../html/main.html");
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if cli::is_requested(&args){
+        std::process::exit(cli::run(&args));
+    }
     let mut app = Application::new();
 /*
     let mut path = std::env::current_exe().unwrap();
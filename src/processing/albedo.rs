@@ -0,0 +1,145 @@
+// diffuse reflectance from the directional takes, flat fielded by the gray card frame
+use super::Image;
+use super::super::error::{Error, Result};
+
+// reflectance of the gray card
+pub const GRAY_CARD_REFLECTANCE: f32 = 0.18;
+// blur radius of the gray frame relative to its width, hides the card texture and noise
+const FALLOFF_BLUR_RATIO: f32 = 0.01;
+
+// the gray frame is the card shot with all lights on at the exposure of the takes,
+// so gray = GRAY_CARD_REFLECTANCE * falloff * sum(light.z) and each take is
+// albedo * falloff * dot(light, normal).
+pub fn solve(takes: &[Option<Image>], lights: &[[f32; 3]], normals: &Image, gray: &Image)->Result<Image>{
+    if !gray.same_size(normals){
+        return Err(Error::processing("solve albedo", "the gray frame and the takes have different sizes"));
+    }
+    let used: Vec<(&Image, &[f32; 3])> = takes.iter().zip(lights.iter())
+        .filter_map(|(take, l)|take.as_ref().map(|img|(img, l)))
+        .collect();
+    if used.is_empty(){
+        return Err(Error::processing("solve albedo", "there are no takes"));
+    }
+    let falloff = box_blur(gray, ((gray.width() as f32 * FALLOFF_BLUR_RATIO) as u32).max(1));
+    let light_sum: f32 = lights.iter().map(|l|l[2].max(0.0)).sum();
+    let mut albedo = Image::new(normals.width(), normals.height());
+    for (i, a) in albedo.pixels_mut().iter_mut().enumerate(){
+        let n = normals.pixels()[i];
+        let mut weighted = [0.0f32; 3];
+        let mut shading = 0.0f32;
+        for (img, l) in &used{
+            let s = (l[0] * n[0] + l[1] * n[1] + l[2] * n[2]).max(0.0);
+            let p = img.pixels()[i];
+            for c in 0..3{
+                weighted[c] += p[c] * s;
+            }
+            shading += s * s;
+        }
+        if shading < 1e-6{
+            continue;
+        }
+        let g = falloff.pixels()[i];
+        for c in 0..3{
+            let reflectance = weighted[c] / shading;
+            a[c] = if g[c] > 1e-6{
+                (reflectance * light_sum * GRAY_CARD_REFLECTANCE / g[c]).min(1.0)
+            }
+            else{
+                0.0
+            };
+        }
+    }
+    Ok(albedo)
+}
+
+// separable box blur with clamped edges
+pub fn box_blur(img: &Image, radius: u32)->Image{
+    let horizontal = blur_pass(img, radius, true);
+    blur_pass(&horizontal, radius, false)
+}
+
+fn blur_pass(img: &Image, radius: u32, horizontal: bool)->Image{
+    let (width, height) = (img.width() as i64, img.height() as i64);
+    let (lines, length) = if horizontal { (height, width) } else { (width, height) };
+    let r = radius as i64;
+    let mut blurred = Image::new(img.width(), img.height());
+    for line in 0..lines{
+        let index = |pos: i64|->usize{
+            let pos = pos.max(0).min(length - 1);
+            if horizontal { (line * width + pos) as usize } else { (pos * width + line) as usize }
+        };
+        // running sum over the window
+        let mut sum = [0.0f64; 3];
+        for pos in -r..=r{
+            let p = img.pixels()[index(pos)];
+            for c in 0..3{
+                sum[c] += p[c] as f64;
+            }
+        }
+        let count = (2 * r + 1) as f64;
+        for pos in 0..length{
+            let out = &mut blurred.pixels_mut()[index(pos)];
+            for c in 0..3{
+                out[c] = (sum[c] / count) as f32;
+            }
+            let add = img.pixels()[index(pos + r + 1)];
+            let sub = img.pixels()[index(pos - r)];
+            for c in 0..3{
+                sum[c] += add[c] as f64 - sub[c] as f64;
+            }
+        }
+    }
+    blurred
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::normal::default_light_directions;
+
+    fn filled(width: u32, height: u32, value: [f32; 3])->Image{
+        let mut img = Image::new(width, height);
+        img.map(|_|value);
+        img
+    }
+
+    #[test]
+    fn solve_recovers_the_reflectance_of_a_flat_sample(){
+        let lights = default_light_directions();
+        let albedo = [0.5, 0.25, 0.1];
+        let normals = filled(16, 16, [0.0, 0.0, 1.0]);
+        // each take is albedo * dot(light, normal), the light falls off to half
+        let falloff = 0.5;
+        let takes: Vec<Option<Image>> = lights.iter().map(|l|{
+            Some(filled(16, 16, [albedo[0] * l[2] * falloff, albedo[1] * l[2] * falloff, albedo[2] * l[2] * falloff]))
+        }).collect();
+        let light_sum: f32 = lights.iter().map(|l|l[2]).sum();
+        let gray = filled(16, 16, [GRAY_CARD_REFLECTANCE * falloff * light_sum; 3]);
+        let solved = solve(&takes, &lights, &normals, &gray).unwrap();
+        for p in solved.pixels(){
+            for c in 0..3{
+                assert!((p[c] - albedo[c]).abs() < 1e-5, "{:?} != {:?}", p, albedo);
+            }
+        }
+    }
+
+    #[test]
+    fn solve_rejects_a_gray_frame_of_another_size(){
+        let lights = default_light_directions();
+        let takes = vec![Some(filled(4, 4, [0.5; 3]))];
+        let result = solve(&takes, &lights, &filled(4, 4, [0.0, 0.0, 1.0]), &filled(8, 8, [0.5; 3]));
+        assert!(matches!(result, Err(Error::Processing{..})));
+    }
+
+    #[test]
+    fn box_blur_averages_over_the_window(){
+        let mut img = Image::new(5, 1);
+        img.set(2, 0, [3.0, 0.0, 0.0]);
+        let blurred = box_blur(&img, 1);
+        let row: Vec<f32> = (0..5).map(|x|blurred.get(x, 0)[0]).collect();
+        assert_eq!(row, vec![0.0, 1.0, 1.0, 1.0, 0.0]);
+        // edges are clamped, a flat image stays flat
+        let flat = box_blur(&filled(6, 4, [0.25; 3]), 2);
+        assert!(flat.pixels().iter().all(|p|(p[0] - 0.25).abs() < 1e-6));
+    }
+}
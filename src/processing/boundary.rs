@@ -0,0 +1,222 @@
+// finds the material sample in the flat lit frame for automatic trimming
+use std::collections::VecDeque;
+use super::{luminance, Image};
+use super::super::error::Result;
+
+// below this the UI should ask the operator to correct the corners
+pub const MIN_CONFIDENCE: f32 = 0.6;
+// the frame is reduced to this long edge before detection
+const DETECT_SIZE: u32 = 512;
+
+pub struct Detection{
+    // same order and coordinates as ProcessingOptions::trim_point
+    pub corners: [(f32, f32); 4],
+    // 0..1, how rectangular and well separated the found region is
+    pub confidence: f32,
+}
+
+// average of the takes, every light on at once
+pub fn flat_frame(takes: &[Option<Image>])->Option<Image>{
+    let used: Vec<&Image> = takes.iter().flatten().collect();
+    let first = used.first()?;
+    let mut flat = Image::new(first.width(), first.height());
+    for img in &used{
+        for (f, p) in flat.pixels_mut().iter_mut().zip(img.pixels()){
+            for c in 0..3{
+                f[c] += p[c] / used.len() as f32;
+            }
+        }
+    }
+    Some(flat)
+}
+
+// the sample is the region around the frame center separated from the background by an otsu threshold
+pub fn detect(flat: &Image)->Result<Detection>{
+    let (width, height, lum) = reduce(flat);
+    let threshold = otsu(&lum);
+    let center = (height / 2 * width + width / 2) as usize;
+    let sample_is_bright = lum[center] > threshold.value;
+    let inside = |i: usize|(lum[i] > threshold.value) == sample_is_bright;
+
+    // flood fill from the center
+    let mut mask = vec![false; lum.len()];
+    let mut queue = VecDeque::new();
+    mask[center] = true;
+    queue.push_back(center);
+    let mut area = 0usize;
+    let mut touches_border = false;
+    // bottom-left, top-left, top-right, bottom-right by the extremes of x - y and x + y
+    let mut best = [(f32::MAX, center), (f32::MAX, center), (f32::MIN, center), (f32::MIN, center)];
+    while let Some(i) = queue.pop_front(){
+        area += 1;
+        let (x, y) = (i % width as usize, i / width as usize);
+        if x == 0 || y == 0 || x + 1 == width as usize || y + 1 == height as usize{
+            touches_border = true;
+        }
+        let (fx, fy) = (x as f32, y as f32);
+        if fx - fy < best[0].0 { best[0] = (fx - fy, i); }
+        if fx + fy < best[1].0 { best[1] = (fx + fy, i); }
+        if fx - fy > best[2].0 { best[2] = (fx - fy, i); }
+        if fx + fy > best[3].0 { best[3] = (fx + fy, i); }
+        let mut neighbors = Vec::with_capacity(4);
+        if x > 0 { neighbors.push(i - 1); }
+        if x + 1 < width as usize { neighbors.push(i + 1); }
+        if y > 0 { neighbors.push(i - width as usize); }
+        if y + 1 < height as usize { neighbors.push(i + width as usize); }
+        for n in neighbors{
+            if !mask[n] && inside(n){
+                mask[n] = true;
+                queue.push_back(n);
+            }
+        }
+    }
+
+    let mut corners = [(0.0, 0.0); 4];
+    for k in 0..4{
+        let i = best[k].1;
+        let (x, y) = (i % width as usize, i / width as usize);
+        // outer edge of the corner pixel
+        let ox = if k >= 2 { 1.0 } else { 0.0 };
+        let oy = if k == 0 || k == 3 { 1.0 } else { 0.0 };
+        corners[k] = ((x as f32 + ox) / width as f32, (y as f32 + oy) / height as f32);
+    }
+    let quad_area = quad_area(&corners) * (width * height) as f32;
+    let rectangularity = if quad_area > 0.0 { (area as f32 / quad_area).min(1.0) } else { 0.0 };
+    let mut confidence = rectangularity * threshold.separation;
+    if touches_border{
+        // most likely the background leaked in or the sample is cut off
+        confidence *= 0.5;
+    }
+    Ok(Detection{corners, confidence})
+}
+
+// luminance of the frame box filtered down to DETECT_SIZE
+fn reduce(img: &Image)->(u32, u32, Vec<f32>){
+    let scale = img.width().max(img.height()).div_ceil(DETECT_SIZE).max(1);
+    let (width, height) = ((img.width() / scale).max(1), (img.height() / scale).max(1));
+    let mut lum = vec![0.0; (width * height) as usize];
+    for y in 0..height{
+        for x in 0..width{
+            let mut sum = 0.0;
+            let mut count = 0;
+            for sy in (y * scale)..((y + 1) * scale).min(img.height()){
+                for sx in (x * scale)..((x + 1) * scale).min(img.width()){
+                    sum += luminance(img.get(sx, sy));
+                    count += 1;
+                }
+            }
+            lum[(y * width + x) as usize] = sum / count.max(1) as f32;
+        }
+    }
+    (width, height, lum)
+}
+
+struct Threshold{
+    value: f32,
+    // between class variance over total variance
+    separation: f32,
+}
+
+fn otsu(lum: &[f32])->Threshold{
+    const BINS: usize = 256;
+    let max = lum.iter().cloned().fold(1e-6f32, f32::max);
+    let mut histogram = [0usize; BINS];
+    for v in lum{
+        histogram[((v / max) * (BINS - 1) as f32) as usize] += 1;
+    }
+    let total = lum.len() as f64;
+    let sum_all: f64 = histogram.iter().enumerate().map(|(i, c)|i as f64 * *c as f64).sum();
+    let mean = sum_all / total;
+    let variance: f64 = histogram.iter().enumerate().map(|(i, c)|(i as f64 - mean).powi(2) * *c as f64).sum::<f64>() / total;
+    let mut weight_low = 0.0;
+    let mut sum_low = 0.0;
+    let mut best = (0usize, 0.0f64);
+    for (i, c) in histogram.iter().enumerate(){
+        weight_low += *c as f64;
+        sum_low += i as f64 * *c as f64;
+        let weight_high = total - weight_low;
+        if weight_low == 0.0 || weight_high == 0.0{
+            continue;
+        }
+        let mean_low = sum_low / weight_low;
+        let mean_high = (sum_all - sum_low) / weight_high;
+        let between = weight_low * weight_high * (mean_low - mean_high).powi(2) / (total * total);
+        if between > best.1{
+            best = (i, between);
+        }
+    }
+    Threshold{
+        // upper edge of the highest bin of the dark class, a bin center would put part of that bin above it
+        value: (best.0 + 1) as f32 / (BINS - 1) as f32 * max,
+        separation: if variance > 0.0 { (best.1 / variance) as f32 } else { 0.0 },
+    }
+}
+
+// shoelace formula
+fn quad_area(corners: &[(f32, f32); 4])->f32{
+    let mut area = 0.0;
+    for i in 0..4{
+        let (x0, y0) = corners[i];
+        let (x1, y1) = corners[(i + 1) % 4];
+        area += x0 * y1 - x1 * y0;
+    }
+    area.abs() * 0.5
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    // a bright sample on a dark background, from (left, top) to (right, bottom) in frame pixels
+    fn frame(width: u32, height: u32, left: u32, top: u32, right: u32, bottom: u32)->Image{
+        let mut img = Image::new(width, height);
+        for y in 0..height{
+            for x in 0..width{
+                let inside = x >= left && x < right && y >= top && y < bottom;
+                img.set(x, y, if inside { [0.8; 3] } else { [0.05; 3] });
+            }
+        }
+        img
+    }
+
+    #[test]
+    fn detect_finds_the_corners_of_a_rectangle(){
+        let detection = detect(&frame(200, 160, 50, 32, 150, 112)).unwrap();
+        let expected = [(0.25, 0.7), (0.25, 0.2), (0.75, 0.2), (0.75, 0.7)];
+        for (corner, expected) in detection.corners.iter().zip(expected.iter()){
+            assert!((corner.0 - expected.0).abs() < 1e-6 && (corner.1 - expected.1).abs() < 1e-6, "{:?} != {:?}", corner, expected);
+        }
+        assert!(detection.confidence > 0.9, "confidence {}", detection.confidence);
+    }
+
+    #[test]
+    fn detect_reduces_large_frames(){
+        // 1024 wide is reduced by 2, the corners stay where they are
+        let detection = detect(&frame(1024, 512, 256, 128, 768, 384)).unwrap();
+        let expected = [(0.25, 0.75), (0.25, 0.25), (0.75, 0.25), (0.75, 0.75)];
+        for (corner, expected) in detection.corners.iter().zip(expected.iter()){
+            assert!((corner.0 - expected.0).abs() < 1e-6 && (corner.1 - expected.1).abs() < 1e-6, "{:?} != {:?}", corner, expected);
+        }
+    }
+
+    #[test]
+    fn a_frame_without_a_sample_has_no_confidence(){
+        let mut flat = Image::new(64, 64);
+        flat.map(|_|[0.5; 3]);
+        assert!(detect(&flat).unwrap().confidence < MIN_CONFIDENCE);
+        // a sample cut off by the frame edge is doubtful
+        let detection = detect(&frame(100, 100, 0, 20, 80, 80)).unwrap();
+        assert!(detection.confidence < MIN_CONFIDENCE, "confidence {}", detection.confidence);
+    }
+
+    #[test]
+    fn flat_frame_averages_the_takes(){
+        let mut a = Image::new(2, 2);
+        a.map(|_|[0.2; 3]);
+        let mut b = Image::new(2, 2);
+        b.map(|_|[0.6; 3]);
+        let flat = flat_frame(&[Some(a), None, Some(b)]).unwrap();
+        assert!(flat.pixels().iter().all(|p|(p[0] - 0.4).abs() < 1e-6));
+        assert!(flat_frame(&[None, None]).is_none());
+    }
+}
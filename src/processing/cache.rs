@@ -0,0 +1,184 @@
+// stage outputs kept in the cache directory of a process. an entry carries the key it was built for,
+// an entry with another key is stale and is overwritten by the next run of its stage.
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use sha2::{Digest, Sha256};
+use super::super::config;
+use super::Image;
+use super::super::error::{Error, Result};
+
+// bump when a stage computes something else from the same inputs, older entries are not used then
+const CACHE_VERSION: u32 = 1;
+const MAGIC: &[u8; 8] = b"TXSCACHE";
+
+// sha-256 of everything a stage output depends on
+pub type Key = [u8; 32];
+
+// builds a key. every value is written with its length or fixed size, so different inputs never run together
+pub struct KeyBuilder{
+    hasher: Sha256,
+}
+
+impl KeyBuilder{
+    pub fn new(stage: &str)->Self{
+        let mut builder = KeyBuilder{hasher: Sha256::new()};
+        builder.u32(CACHE_VERSION);
+        builder.bytes(stage.as_bytes());
+        builder
+    }
+    pub fn bytes(&mut self, bytes: &[u8]){
+        self.hasher.update((bytes.len() as u64).to_le_bytes());
+        self.hasher.update(bytes);
+    }
+    pub fn u32(&mut self, value: u32){
+        self.hasher.update(value.to_le_bytes());
+    }
+    pub fn f32(&mut self, value: f32){
+        self.hasher.update(value.to_le_bytes());
+    }
+    pub fn f64(&mut self, value: f64){
+        self.hasher.update(value.to_le_bytes());
+    }
+    pub fn bool(&mut self, value: bool){
+        self.hasher.update([value as u8]);
+    }
+    pub fn key(&mut self, key: &Key){
+        self.hasher.update(key);
+    }
+    // contents of a file, a missing file is a value of its own
+    pub fn file(&mut self, path: &Path)->Result<()>{
+        match fs::read(path){
+            Ok(contents)=>{
+                self.bool(true);
+                self.bytes(&contents);
+                Ok(())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound=>{
+                self.bool(false);
+                Ok(())
+            }
+            Err(e)=>Err(Error::io(path, "read a processing input", e)),
+        }
+    }
+    pub fn finish(self)->Key{
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&self.hasher.finalize());
+        key
+    }
+}
+
+// how the images of an entry are stored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Precision{
+    // 0..1 values such as the frames, 16 bits are finer than the 8 bit frames and the 8 bit maps
+    U16,
+    // signed or unbounded values such as the normals
+    F32,
+}
+
+// output of a stage: images, missing frames stay None, and a few numbers such as the trim corners
+#[derive(Default)]
+pub struct Entry{
+    pub images: Vec<Option<Image>>,
+    pub values: Vec<f32>,
+}
+
+pub fn entry_path(process_dir: &Path, stage: &str)->PathBuf{
+    process_dir.join(config::PROCESS_CACHE_DIR_NAME).join(format!("{}.bin", stage))
+}
+
+// the entry at path when it was built for key. a missing, stale or damaged entry is None, the stage runs again
+pub fn load(path: &Path, key: &Key)->Option<Entry>{
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let mut header = [0u8; 40];
+    reader.read_exact(&mut header).ok()?;
+    if &header[..8] != MAGIC || &header[8..] != key{
+        return None;
+    }
+    let mut entry = Entry::default();
+    for _ in 0..read_u32(&mut reader)?{
+        entry.values.push(f32::from_bits(read_u32(&mut reader)?));
+    }
+    for _ in 0..read_u32(&mut reader)?{
+        entry.images.push(read_image(&mut reader)?);
+    }
+    Some(entry)
+}
+
+// write entry for key to path. it is written under another name first, an entry that is there is complete
+pub fn store(path: &Path, key: &Key, entry: &Entry, precision: Precision)->Result<()>{
+    if let Some(dir) = path.parent(){
+        fs::create_dir_all(dir).map_err(|e|Error::io(dir, "write the processing cache", e))?;
+    }
+    let part_path = path.with_extension("bin.part");
+    let written = write_entry(&part_path, key, entry, precision)
+        .map_err(|e|Error::io(&part_path, "write the processing cache", e));
+    if let Err(error) = written{
+        let _ = fs::remove_file(&part_path);
+        return Err(error);
+    }
+    fs::rename(&part_path, path).map_err(|e|Error::io(path, "write the processing cache", e))
+}
+
+fn write_entry(path: &Path, key: &Key, entry: &Entry, precision: Precision)->std::io::Result<()>{
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(key)?;
+    writer.write_all(&(entry.values.len() as u32).to_le_bytes())?;
+    for value in &entry.values{
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    writer.write_all(&(entry.images.len() as u32).to_le_bytes())?;
+    for img in &entry.images{
+        let img = match img{
+            Some(img)=>img,
+            None=>{
+                writer.write_all(&[0])?;
+                continue;
+            }
+        };
+        writer.write_all(&[if precision == Precision::U16 { 1 } else { 2 }])?;
+        writer.write_all(&img.width().to_le_bytes())?;
+        writer.write_all(&img.height().to_le_bytes())?;
+        for c in img.pixels().iter().flatten(){
+            match precision{
+                Precision::U16=>writer.write_all(&((c.clamp(0.0, 1.0) * 65535.0 + 0.5) as u16).to_le_bytes())?,
+                Precision::F32=>writer.write_all(&c.to_le_bytes())?,
+            }
+        }
+    }
+    writer.flush()
+}
+
+fn read_u32(reader: &mut impl Read)->Option<u32>{
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes).ok()?;
+    Some(u32::from_le_bytes(bytes))
+}
+
+fn read_u16(reader: &mut impl Read)->Option<u16>{
+    let mut bytes = [0u8; 2];
+    reader.read_exact(&mut bytes).ok()?;
+    Some(u16::from_le_bytes(bytes))
+}
+
+fn read_image(reader: &mut impl Read)->Option<Option<Image>>{
+    let mut tag = [0u8; 1];
+    reader.read_exact(&mut tag).ok()?;
+    if tag[0] == 0{
+        return Some(None);
+    }
+    let (width, height) = (read_u32(reader)?, read_u32(reader)?);
+    let mut img = Image::new(width, height);
+    for p in img.pixels_mut(){
+        for c in p.iter_mut(){
+            *c = match tag[0]{
+                1=>read_u16(reader)? as f32 / 65535.0,
+                2=>f32::from_bits(read_u32(reader)?),
+                _=>return None,
+            };
+        }
+    }
+    Some(Some(img))
+}
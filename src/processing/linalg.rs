@@ -0,0 +1,247 @@
+// small dense helpers for the solvers, everything is f64 and row major
+
+pub type Mat3 = [[f64; 3]; 3];
+
+pub fn det3(m: &Mat3)->f64{
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+pub fn invert3(m: &Mat3)->Option<Mat3>{
+    let det = det3(m);
+    if det.abs() < 1e-12{
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let mut r = [[0.0; 3]; 3];
+    r[0][0] = (m[1][1] * m[2][2] - m[1][2] * m[2][1]) * inv_det;
+    r[0][1] = (m[0][2] * m[2][1] - m[0][1] * m[2][2]) * inv_det;
+    r[0][2] = (m[0][1] * m[1][2] - m[0][2] * m[1][1]) * inv_det;
+    r[1][0] = (m[1][2] * m[2][0] - m[1][0] * m[2][2]) * inv_det;
+    r[1][1] = (m[0][0] * m[2][2] - m[0][2] * m[2][0]) * inv_det;
+    r[1][2] = (m[0][2] * m[1][0] - m[0][0] * m[1][2]) * inv_det;
+    r[2][0] = (m[1][0] * m[2][1] - m[1][1] * m[2][0]) * inv_det;
+    r[2][1] = (m[0][1] * m[2][0] - m[0][0] * m[2][1]) * inv_det;
+    r[2][2] = (m[0][0] * m[1][1] - m[0][1] * m[1][0]) * inv_det;
+    Some(r)
+}
+
+pub fn mul3v(m: &Mat3, v: &[f64; 3])->[f64; 3]{
+    [
+        m[0][0] * v[0] + m[0][1] * v[1] + m[0][2] * v[2],
+        m[1][0] * v[0] + m[1][1] * v[1] + m[1][2] * v[2],
+        m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2],
+    ]
+}
+
+pub fn normalize3(v: [f64; 3])->Option<[f64; 3]>{
+    let len = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    if len < 1e-12{
+        return None;
+    }
+    Some([v[0] / len, v[1] / len, v[2] / len])
+}
+
+// solve a x = b by gaussian elimination with partial pivoting
+pub fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>)->Option<Vec<f64>>{
+    let n = b.len();
+    for col in 0..n{
+        let pivot = (col..n).max_by(|&i, &j|a[i][col].abs().partial_cmp(&a[j][col].abs()).unwrap())?;
+        if a[pivot][col].abs() < 1e-12{
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        for row in (col + 1)..n{
+            let factor = a[row][col] / a[col][col];
+            let (above, below) = a.split_at_mut(row);
+            for (x, p) in below[0][col..n].iter_mut().zip(&above[col][col..n]){
+                *x -= factor * p;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev(){
+        let mut sum = b[row];
+        for k in (row + 1)..n{
+            sum -= a[row][k] * x[k];
+        }
+        x[row] = sum / a[row][row];
+    }
+    Some(x)
+}
+
+// homography taking each src point onto the dst point with the same index
+pub fn homography(src: &[(f64, f64); 4], dst: &[(f64, f64); 4])->Option<Mat3>{
+    let mut a = Vec::with_capacity(8);
+    let mut b = Vec::with_capacity(8);
+    for i in 0..4{
+        let (x, y) = src[i];
+        let (u, v) = dst[i];
+        a.push(vec![x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y]);
+        b.push(u);
+        a.push(vec![0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y]);
+        b.push(v);
+    }
+    let h = solve(a, b)?;
+    Some([[h[0], h[1], h[2]], [h[3], h[4], h[5]], [h[6], h[7], 1.0]])
+}
+
+pub fn project(h: &Mat3, x: f64, y: f64)->(f64, f64){
+    let p = mul3v(h, &[x, y, 1.0]);
+    (p[0] / p[2], p[1] / p[2])
+}
+
+pub fn cross3(a: &[f64; 3], b: &[f64; 3])->[f64; 3]{
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+pub fn dot3(a: &[f64; 3], b: &[f64; 3])->f64{
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn mul3(a: &Mat3, b: &Mat3)->Mat3{
+    let mut r = [[0.0; 3]; 3];
+    for i in 0..3{
+        for j in 0..3{
+            r[i][j] = a[i][0] * b[0][j] + a[i][1] * b[1][j] + a[i][2] * b[2][j];
+        }
+    }
+    r
+}
+
+// eigen decomposition of a symmetric matrix by cyclic jacobi rotations.
+// returns the eigenvalues and the eigenvectors as columns.
+pub fn symmetric_eigen(mut a: Vec<Vec<f64>>)->(Vec<f64>, Vec<Vec<f64>>){
+    let n = a.len();
+    let mut v = vec![vec![0.0; n]; n];
+    for (i, row) in v.iter_mut().enumerate(){
+        row[i] = 1.0;
+    }
+    for _ in 0..100{
+        let off: f64 = (0..n).flat_map(|i|(0..n).filter(move |&j|j != i).map(move |j|(i, j))).map(|(i, j)|a[i][j] * a[i][j]).sum();
+        if off < 1e-30{
+            break;
+        }
+        for p in 0..n{
+            for q in (p + 1)..n{
+                if a[p][q].abs() < 1e-300{
+                    continue;
+                }
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for row in a.iter_mut(){
+                    let (akp, akq) = (row[p], row[q]);
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (above, below) = a.split_at_mut(q);
+                for (apk, aqk) in above[p].iter_mut().zip(below[0].iter_mut()){
+                    let (x, y) = (*apk, *aqk);
+                    *apk = c * x - s * y;
+                    *aqk = s * x + c * y;
+                }
+                for row in v.iter_mut(){
+                    let (vkp, vkq) = (row[p], row[q]);
+                    row[p] = c * vkp - s * vkq;
+                    row[q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+    ((0..n).map(|i|a[i][i]).collect(), v)
+}
+
+// unit x minimising |m x| for a symmetric m = A^T A
+pub fn null_vector(m: Vec<Vec<f64>>)->Vec<f64>{
+    let n = m.len();
+    let (values, vectors) = symmetric_eigen(m);
+    let smallest = (0..n).min_by(|&i, &j|values[i].partial_cmp(&values[j]).unwrap()).unwrap_or(0);
+    (0..n).map(|k|vectors[k][smallest]).collect()
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn assert_close(a: f64, b: f64){
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn solve_finds_the_solution_and_pivots(){
+        let x = solve(vec![vec![2.0, 1.0], vec![1.0, -1.0]], vec![5.0, 1.0]).unwrap();
+        assert_close(x[0], 2.0);
+        assert_close(x[1], 1.0);
+        // the first pivot is zero, the rows have to be swapped
+        let x = solve(vec![vec![0.0, 1.0], vec![1.0, 0.0]], vec![3.0, 4.0]).unwrap();
+        assert_close(x[0], 4.0);
+        assert_close(x[1], 3.0);
+        assert!(solve(vec![vec![1.0, 2.0], vec![2.0, 4.0]], vec![1.0, 2.0]).is_none());
+    }
+
+    #[test]
+    fn invert3_gives_the_inverse(){
+        let m = [[2.0, 0.0, 1.0], [1.0, 3.0, 0.0], [0.0, 1.0, 4.0]];
+        assert_close(det3(&m), 25.0);
+        let product = mul3(&m, &invert3(&m).unwrap());
+        for (i, row) in product.iter().enumerate(){
+            for (j, value) in row.iter().enumerate(){
+                assert_close(*value, if i == j { 1.0 } else { 0.0 });
+            }
+        }
+        assert!(invert3(&[[1.0, 2.0, 3.0], [2.0, 4.0, 6.0], [0.0, 0.0, 1.0]]).is_none());
+    }
+
+    #[test]
+    fn homography_maps_the_unit_square(){
+        let src = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let dst = [(2.0, 3.0), (4.0, 3.0), (4.0, 5.0), (2.0, 5.0)];
+        let h = homography(&src, &dst).unwrap();
+        let expected = [[2.0, 0.0, 2.0], [0.0, 2.0, 3.0], [0.0, 0.0, 1.0]];
+        for (row, expected_row) in h.iter().zip(expected.iter()){
+            for (value, expected) in row.iter().zip(expected_row.iter()){
+                assert_close(*value, *expected);
+            }
+        }
+        // a perspective quad
+        let dst = [(0.0, 0.0), (3.0, 0.5), (2.5, 2.0), (0.5, 1.5)];
+        let h = homography(&src, &dst).unwrap();
+        for (s, d) in src.iter().zip(dst.iter()){
+            let (x, y) = project(&h, s.0, s.1);
+            assert_close(x, d.0);
+            assert_close(y, d.1);
+        }
+    }
+
+    #[test]
+    fn vector_helpers(){
+        assert_eq!(cross3(&[1.0, 0.0, 0.0], &[0.0, 1.0, 0.0]), [0.0, 0.0, 1.0]);
+        assert_close(dot3(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]), 32.0);
+        let n = normalize3([3.0, 0.0, 4.0]).unwrap();
+        assert_close(n[0], 0.6);
+        assert_close(n[2], 0.8);
+        assert!(normalize3([0.0, 0.0, 0.0]).is_none());
+    }
+
+    #[test]
+    fn symmetric_eigen_of_a_known_matrix(){
+        let (mut values, _) = symmetric_eigen(vec![vec![2.0, 1.0], vec![1.0, 2.0]]);
+        values.sort_by(|a, b|a.partial_cmp(b).unwrap());
+        assert_close(values[0], 1.0);
+        assert_close(values[1], 3.0);
+    }
+
+    #[test]
+    fn null_vector_is_the_eigenvector_of_the_smallest_eigenvalue(){
+        let x = null_vector(vec![vec![2.0, 1.0, 0.0], vec![1.0, 2.0, 0.0], vec![0.0, 0.0, 5.0]]);
+        let sign = x[0].signum();
+        assert_close(x[0] * sign, 0.5f64.sqrt());
+        assert_close(x[1] * sign, -(0.5f64.sqrt()));
+        assert_close(x[2], 0.0);
+    }
+}
@@ -0,0 +1,223 @@
+use std::path::{Path, PathBuf};
+use super::config;
+use super::error::{Error, Result};
+
+pub mod linalg;
+pub mod normal;
+pub mod albedo;
+pub mod roughness;
+pub mod trim;
+pub mod boundary;
+pub mod tiling;
+pub mod undistort;
+pub mod status;
+pub mod cache;
+pub mod pipeline;
+
+// output maps in a process directory
+pub const NORMAL_FILE_NAME: &str = "normal.png";
+pub const ALBEDO_FILE_NAME: &str = "albedo.png";
+pub const ROUGHNESS_FILE_NAME: &str = "roughness.png";
+
+// rgb image with f32 channels
+#[derive(Clone)]
+pub struct Image{
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 3]>,
+}
+
+impl Image{
+    pub fn new(width: u32, height: u32)->Self{
+        Image{width, height, pixels: vec![[0.0; 3]; (width * height) as usize]}
+    }
+    // channels are mapped to 0..1 as stored in the file
+    pub fn load(file_name: &Path)->Result<Self>{
+        let reader = image::io::Reader::open(file_name).map_err(|e|Error::io(file_name, "load the image", e))?;
+        let reader = reader.with_guessed_format().map_err(|e|Error::io(file_name, "load the image", e))?;
+        let decoded = reader.decode().map_err(|e|Error::image(file_name, "load the image", e))?;
+        let rgb = decoded.to_rgb16();
+        let pixels = rgb.pixels().map(|p|{
+            [p[0] as f32 / 65535.0, p[1] as f32 / 65535.0, p[2] as f32 / 65535.0]
+        }).collect();
+        Ok(Image{width: rgb.width(), height: rgb.height(), pixels})
+    }
+    // load an srgb encoded file (e.g. a camera jpeg) as linear values
+    pub fn load_linear(file_name: &Path)->Result<Self>{
+        let mut img = Self::load(file_name)?;
+        img.map(|p|[srgb_to_linear(p[0]), srgb_to_linear(p[1]), srgb_to_linear(p[2])]);
+        Ok(img)
+    }
+    // values are clamped to 0..1 and written as they are
+    pub fn save(&self, file_name: &Path)->Result<()>{
+        let mut buffer = Vec::with_capacity(self.pixels.len() * 3);
+        for p in &self.pixels{
+            for c in p{
+                buffer.push((c.clamp(0.0, 1.0) * 255.0 + 0.5) as u8);
+            }
+        }
+        let rgb = image::RgbImage::from_raw(self.width, self.height, buffer)
+            .ok_or_else(||Error::processing("save the image", &format!("{} has an invalid size", file_name.display())))?;
+        rgb.save(file_name).map_err(|e|Error::image(file_name, "save the image", e))
+    }
+    // linear values are written srgb encoded
+    pub fn save_srgb(&self, file_name: &Path)->Result<()>{
+        let mut img = Image{width: self.width, height: self.height, pixels: self.pixels.clone()};
+        img.map(|p|[linear_to_srgb(p[0]), linear_to_srgb(p[1]), linear_to_srgb(p[2])]);
+        img.save(file_name)
+    }
+    pub fn width(&self)->u32{
+        self.width
+    }
+    pub fn height(&self)->u32{
+        self.height
+    }
+    pub fn pixels(&self)->&[[f32; 3]]{
+        &self.pixels
+    }
+    pub fn pixels_mut(&mut self)->&mut [[f32; 3]]{
+        &mut self.pixels
+    }
+    pub fn get(&self, x: u32, y: u32)->[f32; 3]{
+        self.pixels[(y * self.width + x) as usize]
+    }
+    pub fn set(&mut self, x: u32, y: u32, value: [f32; 3]){
+        self.pixels[(y * self.width + x) as usize] = value;
+    }
+    pub fn map<F: Fn([f32; 3])->[f32; 3]>(&mut self, f: F){
+        for p in self.pixels.iter_mut(){
+            *p = f(*p);
+        }
+    }
+    // bilinear sample, pixel i covers i..i+1 and edges are clamped
+    pub fn sample(&self, x: f32, y: f32)->[f32; 3]{
+        let x = (x - 0.5).max(0.0).min((self.width - 1) as f32);
+        let y = (y - 0.5).max(0.0).min((self.height - 1) as f32);
+        let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+        let (p00, p10, p01, p11) = (self.get(x0, y0), self.get(x1, y0), self.get(x0, y1), self.get(x1, y1));
+        let mut p = [0.0; 3];
+        for c in 0..3{
+            let top = p00[c] + (p10[c] - p00[c]) * fx;
+            let bottom = p01[c] + (p11[c] - p01[c]) * fx;
+            p[c] = top + (bottom - top) * fy;
+        }
+        p
+    }
+    pub fn same_size(&self, other: &Image)->bool{
+        self.width == other.width && self.height == other.height
+    }
+}
+
+// rec.709 luminance of a linear rgb value
+pub fn luminance(p: [f32; 3])->f32{
+    0.2126 * p[0] + 0.7152 * p[1] + 0.0722 * p[2]
+}
+
+pub fn srgb_to_linear(v: f32)->f32{
+    if v <= 0.04045{
+        v / 12.92
+    }
+    else{
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(v: f32)->f32{
+    let v = v.max(0.0);
+    if v <= 0.0031308{
+        v * 12.92
+    }
+    else{
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// captured frames of a process, missing files are None
+pub struct Captures{
+    pub gray: Option<Image>,
+    pub takes: Vec<Option<Image>>,
+}
+
+impl Captures{
+    pub fn load(process_dir: &Path)->Result<Self>{
+        let gray = load_if_exists(&process_dir.join(config::GRAY_FILE_NAME))?;
+        let mut takes = Vec::with_capacity(config::TAKE_COUNT);
+        for i in 0..config::TAKE_COUNT{
+            takes.push(load_if_exists(&process_dir.join(config::take_file_name(i)))?);
+        }
+        let captures = Captures{gray, takes};
+        captures.check_size()?;
+        Ok(captures)
+    }
+    // apply a stage to the gray frame and every take alike
+    pub fn map<F: Fn(&Image)->Result<Image>>(&mut self, f: F)->Result<()>{
+        for img in self.gray.iter_mut().chain(self.takes.iter_mut().flatten()){
+            *img = f(img)?;
+        }
+        Ok(())
+    }
+    // all frames have to share one size to stay pixel aligned
+    fn check_size(&self)->Result<()>{
+        let mut frames = self.gray.iter().chain(self.takes.iter().flatten());
+        if let Some(first) = frames.next(){
+            if frames.any(|img|!img.same_size(first)){
+                return Err(Error::processing("load the captures", "captured frames have different sizes"));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn load_if_exists(file_name: &Path)->Result<Option<Image>>{
+    if file_name.exists(){
+        Ok(Some(Image::load_linear(file_name)?))
+    }
+    else{
+        Ok(None)
+    }
+}
+
+// lens model at the focal length the process was shot with.
+// None until the project has a lens calibration, a fallback that was taken is added to warnings.
+fn load_lens_model(process_dir: &Path, warnings: &mut Vec<String>)->Result<Option<undistort::LensModel>>{
+    let calibration_path = match process_dir.parent(){
+        Some(root)=>root.join(config::LENS_CARIBRATION_FILE_NAME),
+        None=>return Ok(None),
+    };
+    if !calibration_path.exists(){
+        return Ok(None);
+    }
+    let mut calibration = config::LensCaribrationInfo::new();
+    calibration.load(&calibration_path)?;
+    // without a recorded focal length the wide end is used
+    let mut state = config::ProcessingState::new();
+    let _ = state.load(&process_dir.join(config::PROCESS_STATE_FILE_NAME));
+    let focal_length = state.get_lens().get_focal_length();
+    if focal_length <= 0.0 && !calibration.get_tele().0.is_empty(){
+        warnings.push(format!("no focal length is recorded for the frames, they are undistorted with the wide end of the calibration ({}mm)", calibration.get_focal_length_wide()));
+    }
+    Ok(undistort::LensModel::at_focal_length(&calibration, focal_length))
+}
+
+// what a processing pass produced
+pub struct Report{
+    pub outputs: Vec<PathBuf>,
+    // set when the trim points were detected in this pass
+    pub trim: Option<boundary::Detection>,
+    // stages taken from the cache instead of being run
+    pub reused: Vec<pipeline::Stage>,
+    // what the operator should know about the pass, e.g. a fallback that was taken
+    pub warnings: Vec<String>,
+}
+
+// called before each stage with its name and the finished fraction of the pass.
+// returning an error stops the pass, e.g. when it was cancelled.
+pub type Progress<'a> = dyn FnMut(&str, f32)->Result<()> + 'a;
+
+// build the texture maps of a process from its captures, see pipeline for what is done again and what is reused.
+// with auto trimming the detected corners are written back to process.json.
+pub fn process(process_dir: &Path, settings: &mut config::ProcessingSettings, progress: &mut Progress)->Result<Report>{
+    pipeline::run(process_dir, settings, progress)
+}
@@ -0,0 +1,169 @@
+// photometric stereo: per-pixel surface normals from the directional takes
+use std::path::Path;
+use super::{config, linalg, luminance, Image};
+use super::super::error::{Error, Result};
+
+// calibrated light directions, looked up in the process directory and then in the project root
+pub const LIGHT_FILE_NAME: &str = "lights.json";
+// elevation of the lights of the default rig
+const DEFAULT_LIGHT_ELEVATION: f32 = 45.0;
+
+// take i is lit from i * 45 degrees counter-clockwise from the right edge of the frame.
+// x points right, y points up and z points towards the camera.
+pub fn default_light_directions()->Vec<[f32; 3]>{
+    let elevation = DEFAULT_LIGHT_ELEVATION.to_radians();
+    (0..config::TAKE_COUNT).map(|i|{
+        let azimuth = (i as f32 * 360.0 / config::TAKE_COUNT as f32).to_radians();
+        [elevation.cos() * azimuth.cos(), elevation.cos() * azimuth.sin(), elevation.sin()]
+    }).collect()
+}
+
+pub fn load_light_directions(process_dir: &Path)->Result<Vec<[f32; 3]>>{
+    let candidates = [Some(process_dir), process_dir.parent()];
+    for dir in candidates.iter().flatten(){
+        let file_name = dir.join(LIGHT_FILE_NAME);
+        if !file_name.exists(){
+            continue;
+        }
+        let mut lights: Vec<[f32; 3]> = Vec::new();
+        config::load_from_json(&mut lights, &file_name)?;
+        if lights.len() != config::TAKE_COUNT{
+            return Err(Error::validation("load the light directions", &format!("{} has {} light directions, {} expected", file_name.display(), lights.len(), config::TAKE_COUNT)));
+        }
+        return lights.iter().map(|l|{
+            linalg::normalize3([l[0] as f64, l[1] as f64, l[2] as f64])
+                .map(|n|[n[0] as f32, n[1] as f32, n[2] as f32])
+                .ok_or_else(||Error::validation("load the light directions", &format!("{} has a zero light direction", file_name.display())))
+        }).collect();
+    }
+    Ok(default_light_directions())
+}
+
+// least squares fit of albedo * normal against the available takes (at least three).
+// the returned image holds unit normals.
+pub fn solve(takes: &[Option<Image>], lights: &[[f32; 3]])->Result<Image>{
+    let used: Vec<(&Image, [f64; 3])> = takes.iter().zip(lights.iter())
+        .filter_map(|(take, l)|take.as_ref().map(|img|(img, [l[0] as f64, l[1] as f64, l[2] as f64])))
+        .collect();
+    if used.len() < 3{
+        return Err(Error::processing("solve normals", &format!("at least 3 takes are needed, {} found", used.len())));
+    }
+    // normal equations share L^T L for every pixel
+    let mut ltl = [[0.0; 3]; 3];
+    for (_, l) in &used{
+        for r in 0..3{
+            for c in 0..3{
+                ltl[r][c] += l[r] * l[c];
+            }
+        }
+    }
+    let inv = linalg::invert3(&ltl).ok_or_else(||Error::processing("solve normals", "light directions of the takes are degenerate"))?;
+    let (width, height) = (used[0].0.width(), used[0].0.height());
+    let mut normals = Image::new(width, height);
+    for (i, n) in normals.pixels_mut().iter_mut().enumerate(){
+        let mut lti = [0.0; 3];
+        for (img, l) in &used{
+            let intensity = luminance(img.pixels()[i]) as f64;
+            for r in 0..3{
+                lti[r] += l[r] * intensity;
+            }
+        }
+        let g = linalg::mul3v(&inv, &lti);
+        *n = match linalg::normalize3(g){
+            Some(v) if v[2] > 0.0 => [v[0] as f32, v[1] as f32, v[2] as f32],
+            // unlit or facing away, leave it flat
+            _ => [0.0, 0.0, 1.0],
+        };
+    }
+    Ok(normals)
+}
+
+// unit normals to a tangent space normal map
+pub fn encode(normals: &Image)->Image{
+    let mut encoded = Image::new(normals.width(), normals.height());
+    for (e, n) in encoded.pixels_mut().iter_mut().zip(normals.pixels()){
+        *e = [n[0] * 0.5 + 0.5, n[1] * 0.5 + 0.5, n[2] * 0.5 + 0.5];
+    }
+    encoded
+}
+
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    // takes of a lambertian sphere with albedo 0.5 filling the frame, and the true normal of each pixel
+    fn sphere(size: u32, lights: &[[f32; 3]])->(Vec<Option<Image>>, Vec<Option<[f32; 3]>>){
+        let (center, radius) = (size as f32 * 0.5, size as f32 * 0.45);
+        let truth: Vec<Option<[f32; 3]>> = (0..size * size).map(|i|{
+            let x = ((i % size) as f32 + 0.5 - center) / radius;
+            let y = (center - ((i / size) as f32 + 0.5)) / radius;
+            let r2 = x * x + y * y;
+            if r2 < 1.0 { Some([x, y, (1.0 - r2).sqrt()]) } else { None }
+        }).collect();
+        let takes = lights.iter().map(|l|{
+            let mut img = Image::new(size, size);
+            for (p, n) in img.pixels_mut().iter_mut().zip(&truth){
+                let v = n.map_or(0.0, |n|0.5 * (l[0] * n[0] + l[1] * n[1] + l[2] * n[2]).max(0.0));
+                *p = [v, v, v];
+            }
+            Some(img)
+        }).collect();
+        (takes, truth)
+    }
+
+    #[test]
+    fn default_lights_go_counterclockwise_from_the_right(){
+        let lights = default_light_directions();
+        assert_eq!(lights.len(), config::TAKE_COUNT);
+        let half = 0.5f32.sqrt();
+        for (light, expected) in [(lights[0], [half, 0.0, half]), (lights[2], [0.0, half, half]), (lights[4], [-half, 0.0, half])].iter(){
+            for c in 0..3{
+                assert!((light[c] - expected[c]).abs() < 1e-6, "{:?} != {:?}", light, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn solve_recovers_the_normals_of_a_lambertian_sphere(){
+        let lights = default_light_directions();
+        let (takes, truth) = sphere(64, &lights);
+        let normals = solve(&takes, &lights).unwrap();
+        let mut checked = 0;
+        for (n, expected) in normals.pixels().iter().zip(&truth){
+            match expected{
+                // an attached shadow on one of the takes biases the least squares fit, those pixels are left out
+                Some(e) if lights.iter().all(|l|l[0] * e[0] + l[1] * e[1] + l[2] * e[2] > 0.0)=>{
+                    for c in 0..3{
+                        assert!((n[c] - e[c]).abs() < 1e-4, "{:?} != {:?}", n, e);
+                    }
+                    checked += 1;
+                }
+                Some(_)=>{}
+                // unlit background stays flat
+                None=>assert_eq!(*n, [0.0, 0.0, 1.0]),
+            }
+        }
+        assert!(checked > 500, "only {} pixels checked", checked);
+    }
+
+    #[test]
+    fn solve_needs_three_takes(){
+        let lights = default_light_directions();
+        let (mut takes, _) = sphere(8, &lights);
+        for take in takes.iter_mut().skip(2){
+            *take = None;
+        }
+        assert!(matches!(solve(&takes, &lights), Err(Error::Processing{..})));
+    }
+
+    #[test]
+    fn encode_maps_unit_vectors_to_colors(){
+        let mut normals = Image::new(2, 1);
+        normals.set(0, 0, [0.0, 0.0, 1.0]);
+        normals.set(1, 0, [-1.0, 0.0, 0.0]);
+        let encoded = encode(&normals);
+        assert_eq!(encoded.get(0, 0), [0.5, 0.5, 1.0]);
+        assert_eq!(encoded.get(1, 0), [0.0, 0.5, 0.5]);
+    }
+}
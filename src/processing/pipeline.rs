@@ -0,0 +1,449 @@
+// the processing stages as a graph. every stage has a key made from its own settings and the keys of the
+// stages it reads, its output is cached under that key. a run starts at export and only goes up the graph
+// as far as the cache is stale, so changing the tiling blend does not undistort and trim again.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use super::super::config;
+use super::super::error::{Error, Result};
+use super::cache::{self, Entry, Key, KeyBuilder, Precision};
+use super::undistort::{self, LensModel};
+use super::{albedo, boundary, normal, roughness, tiling, trim, Captures, Progress, Report};
+use super::{ALBEDO_FILE_NAME, NORMAL_FILE_NAME, ROUGHNESS_FILE_NAME};
+
+// cache entry holding only the key of the options the maps were exported with, see exported_with
+const OPTIONS_ENTRY: &str = "options";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage{
+    // the captured frames, undistorted when the project has a lens calibration. gray first, then the takes
+    Undistort,
+    // the frames rectified to the sample, with the corners found by auto trimming as values
+    Trim,
+    Normal,
+    Albedo,
+    Roughness,
+    // normal, albedo and roughness made tileable
+    Tile,
+    // the maps written as png, caches nothing but its key
+    Export,
+}
+
+impl Stage{
+    // every stage after the ones it reads
+    pub const ALL: [Stage; 7] = [Stage::Undistort, Stage::Trim, Stage::Normal, Stage::Albedo, Stage::Roughness, Stage::Tile, Stage::Export];
+
+    pub fn name(&self)->&'static str{
+        match self{
+            Stage::Undistort=>"undistort",
+            Stage::Trim=>"trim",
+            Stage::Normal=>"normal",
+            Stage::Albedo=>"albedo",
+            Stage::Roughness=>"roughness",
+            Stage::Tile=>"tile",
+            Stage::Export=>"export",
+        }
+    }
+    // the stages whose output this one reads
+    pub fn inputs(&self)->&'static [Stage]{
+        match self{
+            Stage::Undistort=>&[],
+            Stage::Trim=>&[Stage::Undistort],
+            Stage::Normal=>&[Stage::Trim],
+            Stage::Albedo | Stage::Roughness=>&[Stage::Trim, Stage::Normal],
+            Stage::Tile=>&[Stage::Normal, Stage::Albedo, Stage::Roughness],
+            Stage::Export=>&[Stage::Tile],
+        }
+    }
+    // shown as the progress while the stage runs
+    fn label(&self)->&'static str{
+        match self{
+            Stage::Undistort=>"undistorting",
+            Stage::Trim=>"trimming",
+            Stage::Normal=>"solving normals",
+            Stage::Albedo=>"solving albedo",
+            Stage::Roughness=>"fitting roughness",
+            Stage::Tile=>"tiling",
+            Stage::Export=>"saving",
+        }
+    }
+    fn precision(&self)->Precision{
+        match self{
+            Stage::Undistort | Stage::Trim=>Precision::U16,
+            _=>Precision::F32,
+        }
+    }
+}
+
+struct Pipeline<'a>{
+    process_dir: &'a Path,
+    options: &'a config::ProcessingOptions,
+    lens: Option<LensModel>,
+    lights: Vec<[f32; 3]>,
+    keys: HashMap<Stage, Key>,
+    outputs: HashMap<Stage, Rc<Entry>>,
+    reused: Vec<Stage>,
+    warnings: Vec<String>,
+}
+
+impl<'a> Pipeline<'a>{
+    fn new(process_dir: &'a Path, options: &'a config::ProcessingOptions)->Result<Self>{
+        let mut warnings = Vec::new();
+        let mut pipeline = Pipeline{
+            process_dir,
+            options,
+            lens: super::load_lens_model(process_dir, &mut warnings)?,
+            lights: normal::load_light_directions(process_dir)?,
+            keys: HashMap::new(),
+            outputs: HashMap::new(),
+            reused: Vec::new(),
+            warnings,
+        };
+        for stage in Stage::ALL.iter(){
+            let key = pipeline.stage_key(*stage)?;
+            pipeline.keys.insert(*stage, key);
+        }
+        Ok(pipeline)
+    }
+    // what the output of stage depends on besides the stages it reads
+    fn stage_key(&self, stage: Stage)->Result<Key>{
+        let mut key = KeyBuilder::new(stage.name());
+        for input in stage.inputs(){
+            key.key(&self.keys[input]);
+        }
+        match stage{
+            Stage::Undistort=>{
+                key.file(&self.process_dir.join(config::GRAY_FILE_NAME))?;
+                for i in 0..config::TAKE_COUNT{
+                    key.file(&self.process_dir.join(config::take_file_name(i)))?;
+                }
+                key.bool(self.lens.is_some());
+                for parameter in self.lens.iter().flat_map(|lens|lens.parameters().to_vec()){
+                    key.f64(parameter);
+                }
+            }
+            Stage::Normal | Stage::Albedo | Stage::Roughness=>{
+                for light in &self.lights{
+                    for c in light{
+                        key.f32(*c);
+                    }
+                }
+            }
+            Stage::Trim | Stage::Tile | Stage::Export=>{}
+        }
+        option_key(&mut key, stage, self.options);
+        Ok(key.finish())
+    }
+    // stages that only pass their input on are not worth the disk space.
+    // the undistorted frames are full resolution, over a gigabyte for 24 megapixels, the trimmed ones are kept instead
+    fn is_cached(&self, stage: Stage)->bool{
+        match stage{
+            Stage::Undistort=>false,
+            Stage::Tile=>self.options.get_tiling(),
+            _=>true,
+        }
+    }
+    // the cached output of stage when it was built from the current inputs
+    fn cached(&self, stage: Stage)->Option<Entry>{
+        if !self.is_cached(stage){
+            return None;
+        }
+        let entry = cache::load(&cache::entry_path(self.process_dir, stage.name()), &self.keys[&stage])?;
+        // the maps have to be there as well, they may have been deleted by hand
+        if stage == Stage::Export && !output_paths(self.process_dir).iter().all(|path|path.exists()){
+            return None;
+        }
+        Some(entry)
+    }
+    // the output of stage, from the cache when its key matches, else run after the stages it reads
+    fn evaluate(&mut self, stage: Stage, progress: &mut Progress)->Result<Rc<Entry>>{
+        if let Some(entry) = self.outputs.get(&stage){
+            return Ok(entry.clone());
+        }
+        let entry = match self.cached(stage){
+            Some(entry)=>{
+                self.reused.push(stage);
+                Rc::new(entry)
+            }
+            None=>{
+                let mut inputs = Vec::new();
+                for input in stage.inputs(){
+                    inputs.push(self.evaluate(*input, progress)?);
+                }
+                let position = Stage::ALL.iter().position(|s|*s == stage).unwrap_or(0);
+                // without a lens calibration the frames are only loaded
+                let label = if stage == Stage::Undistort && self.lens.is_none() { "loading captures" } else { stage.label() };
+                progress(label, 0.1 + 0.9 * position as f32 / Stage::ALL.len() as f32)?;
+                let entry = Rc::new(self.run(stage, &inputs)?);
+                if self.is_cached(stage){
+                    let path = cache::entry_path(self.process_dir, stage.name());
+                    cache::store(&path, &self.keys[&stage], &entry, stage.precision())?;
+                }
+                entry
+            }
+        };
+        self.outputs.insert(stage, entry.clone());
+        Ok(entry)
+    }
+    fn run(&self, stage: Stage, inputs: &[Rc<Entry>])->Result<Entry>{
+        match stage{
+            Stage::Undistort=>{
+                let mut captures = Captures::load(self.process_dir)?;
+                if let Some(model) = &self.lens{
+                    captures.map(|img|undistort::undistort(img, model))?;
+                }
+                let mut images = vec![captures.gray];
+                images.extend(captures.takes);
+                Ok(Entry{images, values: Vec::new()})
+            }
+            Stage::Trim=>{
+                let frames = &inputs[0].images;
+                let mut values = Vec::new();
+                let corners = if self.options.get_auto_trimming(){
+                    let flat = boundary::flat_frame(&frames[1..]).ok_or_else(||Error::processing("trim the frames", "there are no takes to find the sample in"))?;
+                    let detection = boundary::detect(&flat)?;
+                    for (x, y) in detection.corners.iter(){
+                        values.push(*x);
+                        values.push(*y);
+                    }
+                    values.push(detection.confidence);
+                    detection.corners
+                }
+                else{
+                    *self.options.get_trim_points()
+                };
+                let mut images = Vec::with_capacity(frames.len());
+                for frame in frames{
+                    images.push(match frame{
+                        Some(img)=>Some(trim::rectify(img, &corners, self.options.get_trim_resolution())?),
+                        None=>None,
+                    });
+                }
+                Ok(Entry{images, values})
+            }
+            Stage::Normal=>{
+                let normals = normal::solve(&inputs[0].images[1..], &self.lights)?;
+                Ok(Entry{images: vec![Some(normals)], values: Vec::new()})
+            }
+            Stage::Albedo=>{
+                let (frames, normals) = (&inputs[0].images, single_image(&inputs[1])?);
+                let gray = frames[0].as_ref().ok_or_else(||Error::processing("solve albedo", &format!("{} is missing", config::GRAY_FILE_NAME)))?;
+                let albedo = albedo::solve(&frames[1..], &self.lights, normals, gray)?;
+                Ok(Entry{images: vec![Some(albedo)], values: Vec::new()})
+            }
+            Stage::Roughness=>{
+                let roughness = roughness::solve(&inputs[0].images[1..], &self.lights, single_image(&inputs[1])?)?;
+                Ok(Entry{images: vec![Some(roughness)], values: Vec::new()})
+            }
+            Stage::Tile=>{
+                let (normals, albedo, roughness) = (single_image(&inputs[0])?, single_image(&inputs[1])?, single_image(&inputs[2])?);
+                let images = if self.options.get_tiling(){
+                    let blend = self.options.get_tiling_blend();
+                    vec![tiling::tile_normals(normals, blend)?, tiling::tile(albedo, blend)?, tiling::tile(roughness, blend)?]
+                }
+                else{
+                    vec![normals.clone(), albedo.clone(), roughness.clone()]
+                };
+                Ok(Entry{images: images.into_iter().map(Some).collect(), values: Vec::new()})
+            }
+            Stage::Export=>{
+                let maps = &inputs[0].images;
+                let paths = output_paths(self.process_dir);
+                let missing = ||Error::processing("save the maps", "a map to export is missing");
+                normal::encode(maps[0].as_ref().ok_or_else(missing)?).save(&paths[0])?;
+                maps[1].as_ref().ok_or_else(missing)?.save_srgb(&paths[1])?;
+                maps[2].as_ref().ok_or_else(missing)?.save(&paths[2])?;
+                Ok(Entry::default())
+            }
+        }
+    }
+}
+
+// the processing options stage reads
+fn option_key(key: &mut KeyBuilder, stage: Stage, options: &config::ProcessingOptions){
+    match stage{
+        Stage::Trim=>{
+            // auto trimming finds the corners in the undistorted frames, the stored ones do not matter then
+            key.bool(options.get_auto_trimming());
+            if !options.get_auto_trimming(){
+                for (x, y) in options.get_trim_points().iter(){
+                    key.f32(*x);
+                    key.f32(*y);
+                }
+            }
+            key.u32(options.get_trim_resolution());
+        }
+        Stage::Tile=>{
+            key.bool(options.get_tiling());
+            if options.get_tiling(){
+                key.f32(options.get_tiling_blend());
+            }
+        }
+        _=>{}
+    }
+}
+
+// the options of every stage, what the maps depend on in process.json
+fn options_key(options: &config::ProcessingOptions)->Key{
+    let mut key = KeyBuilder::new(OPTIONS_ENTRY);
+    for stage in Stage::ALL.iter(){
+        option_key(&mut key, *stage, options);
+    }
+    key.finish()
+}
+
+// whether the maps of process_dir were exported with options. process.json also holds the exposure,
+// which the maps do not depend on. None when no run recorded it, e.g. after the cache was deleted
+pub fn exported_with(process_dir: &Path, options: &config::ProcessingOptions)->Option<bool>{
+    let path = cache::entry_path(process_dir, OPTIONS_ENTRY);
+    if !path.exists(){
+        return None;
+    }
+    Some(cache::load(&path, &options_key(options)).is_some())
+}
+
+fn single_image(entry: &Entry)->Result<&super::Image>{
+    entry.images.first().and_then(|img|img.as_ref()).ok_or_else(||Error::processing("process the captures", "a stage output is missing its image"))
+}
+
+fn output_paths(process_dir: &Path)->[PathBuf; 3]{
+    [process_dir.join(NORMAL_FILE_NAME), process_dir.join(ALBEDO_FILE_NAME), process_dir.join(ROUGHNESS_FILE_NAME)]
+}
+
+// bring the maps of process_dir up to date, running only the stages whose inputs or options changed.
+// with auto trimming the detected corners are written back to process.json.
+pub fn run(process_dir: &Path, settings: &mut config::ProcessingSettings, progress: &mut Progress)->Result<Report>{
+    progress("checking the cache", 0.0)?;
+    let options = settings.get_options().clone();
+    let mut pipeline = Pipeline::new(process_dir, &options)?;
+    if pipeline.cached(Stage::Export).is_none(){
+        pipeline.evaluate(Stage::Tile, progress)?;
+    }
+    let trim = match pipeline.outputs.get(&Stage::Trim){
+        Some(entry) if entry.values.len() == 9=>{
+            let v = &entry.values;
+            Some(boundary::Detection{corners: [(v[0], v[1]), (v[2], v[3]), (v[4], v[5]), (v[6], v[7])], confidence: v[8]})
+        }
+        _=>None,
+    };
+    // before the export, so the maps are not older than process.json.
+    // only when the corners moved, rewriting it unchanged would show the maps as stale in the process list
+    if let Some(detection) = &trim{
+        if *settings.get_options().get_trim_points() != detection.corners{
+            settings.get_options_mut().set_trim_points(detection.corners);
+            settings.save(&process_dir.join(config::PROCESS_SETTING_FILE_NAME))?;
+        }
+    }
+    pipeline.evaluate(Stage::Export, progress)?;
+    cache::store(&cache::entry_path(process_dir, OPTIONS_ENTRY), &options_key(settings.get_options()), &Entry::default(), Precision::F32)?;
+    Ok(Report{outputs: output_paths(process_dir).to_vec(), trim, reused: pipeline.reused, warnings: pipeline.warnings})
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::super::testing::{self, TempDir};
+
+    // the progress labels of a run, one for every stage that ran
+    fn process(process_dir: &Path)->(Report, Vec<String>){
+        let mut settings = testing::load_settings(process_dir);
+        let mut labels = Vec::new();
+        let report = run(process_dir, &mut settings, &mut |label, _|{
+            labels.push(label.to_string());
+            Ok(())
+        }).unwrap();
+        labels.retain(|label|label != "checking the cache");
+        (report, labels)
+    }
+
+    fn every_stage()->Vec<&'static str>{
+        vec!["loading captures", "trimming", "solving normals", "solving albedo", "fitting roughness", "tiling", "saving"]
+    }
+
+    #[test]
+    fn a_first_run_runs_every_stage_and_a_second_one_none(){
+        let dir = TempDir::new("pipeline_first");
+        let process_dir = dir.path().join("sample");
+        testing::write_process(&process_dir, serde_json::json!({}));
+        let (report, labels) = process(&process_dir);
+        assert_eq!(labels, every_stage());
+        assert!(report.reused.is_empty());
+        assert!(report.outputs.iter().all(|path|path.exists()));
+        let (report, labels) = process(&process_dir);
+        assert!(labels.is_empty(), "{:?}", labels);
+        assert_eq!(report.reused, vec![Stage::Export]);
+    }
+
+    #[test]
+    fn the_tiling_blend_reruns_tile_and_export_only(){
+        let dir = TempDir::new("pipeline_blend");
+        let process_dir = dir.path().join("sample");
+        testing::write_process(&process_dir, serde_json::json!({}));
+        process(&process_dir);
+        testing::set_options(&process_dir, serde_json::json!({"tiling_blend": 0.25}));
+        let (report, labels) = process(&process_dir);
+        assert_eq!(labels, vec!["tiling", "saving"]);
+        // the maps are read from the cache, the frames and the trimming behind them are not needed
+        assert_eq!(report.reused, vec![Stage::Normal, Stage::Albedo, Stage::Roughness]);
+    }
+
+    #[test]
+    fn another_frame_reruns_every_stage(){
+        let dir = TempDir::new("pipeline_frame");
+        let process_dir = dir.path().join("sample");
+        testing::write_process(&process_dir, serde_json::json!({}));
+        process(&process_dir);
+        let mut take = super::super::Image::load(&process_dir.join(config::take_file_name(3))).unwrap();
+        take.map(|p|[p[0] * 0.5, p[1] * 0.5, p[2] * 0.5]);
+        take.save(&process_dir.join(config::take_file_name(3))).unwrap();
+        let (report, labels) = process(&process_dir);
+        assert_eq!(labels, every_stage());
+        assert!(report.reused.is_empty());
+        // the time alone does not count, the contents are the key
+        testing::touch_later(&process_dir.join(config::take_file_name(3)));
+        assert!(process(&process_dir).1.is_empty());
+    }
+
+    #[test]
+    fn a_damaged_entry_is_built_again(){
+        let dir = TempDir::new("pipeline_damaged");
+        let process_dir = dir.path().join("sample");
+        testing::write_process(&process_dir, serde_json::json!({}));
+        process(&process_dir);
+        // cut short in the middle of the images, and not a cache entry at all
+        let truncate = |stage: Stage|{
+            let path = cache::entry_path(&process_dir, stage.name());
+            let length = std::fs::metadata(&path).unwrap().len();
+            std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(length / 2).unwrap();
+        };
+        truncate(Stage::Trim);
+        truncate(Stage::Tile);
+        std::fs::write(cache::entry_path(&process_dir, Stage::Normal.name()), b"garbage").unwrap();
+        // a deleted map makes the export run again, which gets to the damaged entries
+        std::fs::remove_file(process_dir.join(NORMAL_FILE_NAME)).unwrap();
+        let (report, labels) = process(&process_dir);
+        assert_eq!(labels, vec!["loading captures", "trimming", "solving normals", "tiling", "saving"]);
+        assert_eq!(report.reused, vec![Stage::Albedo, Stage::Roughness]);
+        // and they are whole again
+        let settings = testing::load_settings(&process_dir);
+        let pipeline = Pipeline::new(&process_dir, settings.get_options()).unwrap();
+        for stage in [Stage::Trim, Stage::Normal, Stage::Tile].iter(){
+            assert!(pipeline.cached(*stage).is_some(), "{} was not stored again", stage.name());
+        }
+        assert!(process(&process_dir).1.is_empty());
+    }
+
+    #[test]
+    fn the_full_resolution_frames_are_not_cached(){
+        let dir = TempDir::new("pipeline_undistort");
+        let process_dir = dir.path().join("sample");
+        testing::write_process(&process_dir, serde_json::json!({}));
+        let mut calibration = config::LensCaribrationInfo::new();
+        calibration.set_wide(24.0, vec![20.0, 0.0, 8.0, 0.0, 20.0, 8.0, 0.0, 0.0, 1.0], vec![-0.1, 0.0, 0.0, 0.0, 0.0]);
+        calibration.save(&dir.path().join(config::LENS_CARIBRATION_FILE_NAME)).unwrap();
+        let (_, labels) = process(&process_dir);
+        assert_eq!(labels[0], "undistorting");
+        assert!(!cache::entry_path(&process_dir, Stage::Undistort.name()).exists());
+        assert!(cache::entry_path(&process_dir, Stage::Trim.name()).exists());
+    }
+}
@@ -0,0 +1,184 @@
+// roughness from the spread of the specular lobe across the light directions
+use super::{luminance, Image};
+use super::super::error::{Error, Result};
+
+// candidate perceptual roughness values, the shader squares them into the GGX alpha
+const ROUGHNESS_STEPS: usize = 20;
+// residual energy below this fraction of the diffuse energy is treated as no highlight at all
+const SPECULAR_THRESHOLD: f32 = 0.01;
+// viewer looks straight down at the sample
+const VIEW: [f32; 3] = [0.0, 0.0, 1.0];
+
+// the takes are fitted with a lambertian term first, what is left above it is
+// fitted with ks * D_GGX(n.h, alpha) and the best alpha wins.
+// the result holds perceptual roughness in every channel, as uRoughness expects.
+pub fn solve(takes: &[Option<Image>], lights: &[[f32; 3]], normals: &Image)->Result<Image>{
+    let used: Vec<(&Image, &[f32; 3])> = takes.iter().zip(lights.iter())
+        .filter_map(|(take, l)|take.as_ref().map(|img|(img, l)))
+        .collect();
+    if used.len() < 3{
+        return Err(Error::processing("fit roughness", &format!("at least 3 takes are needed, {} found", used.len())));
+    }
+    let halves: Vec<[f32; 3]> = used.iter().map(|(_, l)|normalize([l[0] + VIEW[0], l[1] + VIEW[1], l[2] + VIEW[2]])).collect();
+    let candidates: Vec<f32> = (1..=ROUGHNESS_STEPS).map(|i|i as f32 / ROUGHNESS_STEPS as f32).collect();
+    let mut roughness = Image::new(normals.width(), normals.height());
+    let mut intensities = vec![0.0f32; used.len()];
+    let mut shading = vec![0.0f32; used.len()];
+    let mut residuals = vec![0.0f32; used.len()];
+    let mut ndoth = vec![0.0f32; used.len()];
+    for (i, out) in roughness.pixels_mut().iter_mut().enumerate(){
+        let n = normals.pixels()[i];
+        for (k, (img, l)) in used.iter().enumerate(){
+            intensities[k] = luminance(img.pixels()[i]);
+            shading[k] = dot(n, **l).max(0.0);
+            ndoth[k] = dot(n, halves[k]).max(0.0);
+        }
+        let diffuse = fit_lambert(&intensities, &shading);
+        let mut specular_energy = 0.0;
+        let mut diffuse_energy = 0.0;
+        for k in 0..used.len(){
+            residuals[k] = (intensities[k] - diffuse * shading[k]).max(0.0);
+            specular_energy += residuals[k] * residuals[k];
+            diffuse_energy += (diffuse * shading[k]).powi(2);
+        }
+        let value = if specular_energy <= diffuse_energy * SPECULAR_THRESHOLD{
+            1.0
+        }
+        else{
+            fit_lobe(&residuals, &ndoth, &candidates)
+        };
+        *out = [value, value, value];
+    }
+    Ok(roughness)
+}
+
+// lambertian fit that drops takes lit well above the first fit, those carry the highlight
+fn fit_lambert(intensities: &[f32], shading: &[f32])->f32{
+    let first = lambert(intensities, shading, |_|true);
+    let refit = lambert(intensities, shading, |k|intensities[k] <= first * shading[k] * 1.1);
+    if refit > 0.0 { refit } else { first }
+}
+
+fn lambert<F: Fn(usize)->bool>(intensities: &[f32], shading: &[f32], use_take: F)->f32{
+    let mut num = 0.0;
+    let mut den = 0.0;
+    let mut count = 0;
+    for k in 0..intensities.len(){
+        if use_take(k){
+            num += intensities[k] * shading[k];
+            den += shading[k] * shading[k];
+            count += 1;
+        }
+    }
+    if count < 3 || den < 1e-6{
+        return 0.0;
+    }
+    num / den
+}
+
+// perceptual roughness with the least squared error, ties go to the rougher candidate
+fn fit_lobe(residuals: &[f32], ndoth: &[f32], candidates: &[f32])->f32{
+    let mut best = 1.0;
+    let mut best_error = f32::MAX;
+    for &roughness in candidates{
+        let alpha = roughness * roughness;
+        let mut rd = 0.0;
+        let mut dd = 0.0;
+        for k in 0..residuals.len(){
+            let d = d_ggx(ndoth[k], alpha);
+            rd += residuals[k] * d;
+            dd += d * d;
+        }
+        let ks = if dd > 0.0 { (rd / dd).max(0.0) } else { 0.0 };
+        let error: f32 = residuals.iter().zip(ndoth.iter())
+            .map(|(r, h)|(r - ks * d_ggx(*h, alpha)).powi(2))
+            .sum();
+        if error <= best_error{
+            best_error = error;
+            best = roughness;
+        }
+    }
+    best
+}
+
+// same distribution as D_GGX in the preview shader
+fn d_ggx(ndoth: f32, alpha: f32)->f32{
+    let a2 = alpha * alpha;
+    let d = (ndoth * a2 - ndoth) * ndoth + 1.0;
+    a2 / (d * d + 0.0000001) / std::f32::consts::PI
+}
+
+fn dot(a: [f32; 3], b: [f32; 3])->f32{
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(v: [f32; 3])->[f32; 3]{
+    let len = dot(v, v).sqrt();
+    [v[0] / len, v[1] / len, v[2] / len]
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    // lights at different elevations, so the half vectors of a flat sample spread over the lobe
+    fn lights()->Vec<[f32; 3]>{
+        (0..8).map(|i|{
+            let elevation = (20.0 + i as f32 * 9.0).to_radians();
+            let azimuth = (i as f32 * 45.0).to_radians();
+            [elevation.cos() * azimuth.cos(), elevation.cos() * azimuth.sin(), elevation.sin()]
+        }).collect()
+    }
+
+    // takes of a flat sample, diffuse plus ks times the ggx lobe of roughness
+    fn takes(lights: &[[f32; 3]], ks: f32, roughness: f32)->Vec<Option<Image>>{
+        lights.iter().map(|l|{
+            let h = normalize([l[0] + VIEW[0], l[1] + VIEW[1], l[2] + VIEW[2]]);
+            let v = 0.4 * l[2] + ks * d_ggx(h[2], roughness * roughness);
+            let mut img = Image::new(2, 2);
+            img.map(|_|[v, v, v]);
+            Some(img)
+        }).collect()
+    }
+
+    fn flat_normals()->Image{
+        let mut normals = Image::new(2, 2);
+        normals.map(|_|[0.0, 0.0, 1.0]);
+        normals
+    }
+
+    #[test]
+    fn a_matte_sample_is_fully_rough(){
+        let lights = lights();
+        let roughness = solve(&takes(&lights, 0.0, 0.5), &lights, &flat_normals()).unwrap();
+        assert!(roughness.pixels().iter().all(|p|*p == [1.0, 1.0, 1.0]));
+    }
+
+    #[test]
+    fn the_lobe_of_a_glossy_sample_is_found(){
+        let lights = lights();
+        // the lambertian fit takes up a little of the lobe, the answer is within one candidate step
+        let step = 1.0 / ROUGHNESS_STEPS as f32;
+        for &expected in [0.2f32, 0.3].iter(){
+            let roughness = solve(&takes(&lights, 0.05, expected), &lights, &flat_normals()).unwrap();
+            for p in roughness.pixels(){
+                assert!((p[0] - expected).abs() <= step + 1e-6, "{} for {}", p[0], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn d_ggx_matches_the_shader(){
+        // at the peak D = 1 / (pi alpha^2)
+        assert!((d_ggx(1.0, 0.5) - 1.0 / (std::f32::consts::PI * 0.25)).abs() < 1e-4);
+        assert!(d_ggx(0.5, 0.1) < d_ggx(0.5, 0.5));
+    }
+
+    #[test]
+    fn solve_needs_three_takes(){
+        let lights = lights();
+        let mut takes = takes(&lights, 0.0, 0.5);
+        takes.truncate(2);
+        assert!(matches!(solve(&takes, &lights, &flat_normals()), Err(Error::Processing{..})));
+    }
+}
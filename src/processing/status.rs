@@ -0,0 +1,177 @@
+// what a process directory holds, read from the files themselves rather than from state.json,
+// so frames copied in by hand or maps deleted outside tex_shooter show up as they are.
+use std::path::Path;
+use std::time::SystemTime;
+use serde::Serialize;
+use super::super::config::{self, manifest::ProcessStatus};
+use super::{normal, pipeline, ALBEDO_FILE_NAME, NORMAL_FILE_NAME, ROUGHNESS_FILE_NAME};
+
+// an output map against the files it is built from
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MapState{
+    Missing,
+    // older than a capture or a setting it is built from
+    Stale,
+    Current,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ProcessScan{
+    // directory name of the process
+    pub name: String,
+    pub has_gray: bool,
+    pub has_takes: [bool; config::TAKE_COUNT],
+    // the project has a lens calibration the frames are undistorted with
+    pub calibrated: bool,
+    // trim points are set, by detection or by hand, instead of the full frame
+    pub trimmed: bool,
+    pub normal: MapState,
+    pub albedo: MapState,
+    pub roughness: MapState,
+    pub status: ProcessStatus,
+}
+
+// look at the files of process_dir. an unreadable file counts as missing, a scan does not fail
+pub fn scan(process_dir: &Path)->ProcessScan{
+    let has_gray = process_dir.join(config::GRAY_FILE_NAME).exists();
+    let mut has_takes = [false; config::TAKE_COUNT];
+    for (i, taken) in has_takes.iter_mut().enumerate(){
+        *taken = process_dir.join(config::take_file_name(i)).exists();
+    }
+    let calibrated = matches!(super::load_lens_model(process_dir, &mut Vec::new()), Ok(Some(_)));
+    let mut settings = config::ProcessingSettings::new();
+    let trimmed = settings.load(&process_dir.join(config::PROCESS_SETTING_FILE_NAME)).is_ok()
+        && settings.get_options().has_trim_points();
+    // process.json is rewritten for every exposure change, its options are compared instead of its time when the cache tells them
+    let exported_with = pipeline::exported_with(process_dir, settings.get_options());
+    let inputs = latest_input(process_dir, exported_with.is_none());
+    let map_state = |file_name: &str|match modified(&process_dir.join(file_name)){
+        None=>MapState::Missing,
+        Some(_) if exported_with == Some(false)=>MapState::Stale,
+        Some(time) if inputs.is_some_and(|inputs|time < inputs)=>MapState::Stale,
+        Some(_)=>MapState::Current,
+    };
+    let (normal, albedo, roughness) = (map_state(NORMAL_FILE_NAME), map_state(ALBEDO_FILE_NAME), map_state(ROUGHNESS_FILE_NAME));
+    let shot = has_takes.iter().filter(|taken|**taken).count() + has_gray as usize;
+    let status = if [normal, albedo, roughness].iter().all(|state|*state == MapState::Current){
+        ProcessStatus::Processed
+    }
+    else if shot == config::TAKE_COUNT + 1{
+        ProcessStatus::Captured
+    }
+    else if shot > 0{
+        ProcessStatus::Capturing
+    }
+    else{
+        ProcessStatus::New
+    };
+    ProcessScan{
+        name: process_dir.file_name().map(|name|name.to_string_lossy().into_owned()).unwrap_or_default(),
+        has_gray,
+        has_takes,
+        calibrated,
+        trimmed,
+        normal,
+        albedo,
+        roughness,
+        status,
+    }
+}
+
+// newest of the files the maps are built from: the frames, the settings and what lies in the root for every process.
+// with_settings takes process.json in as well
+fn latest_input(process_dir: &Path, with_settings: bool)->Option<SystemTime>{
+    let mut inputs = vec![
+        process_dir.join(config::GRAY_FILE_NAME),
+        process_dir.join(config::PROCESS_STATE_FILE_NAME),
+        process_dir.join(normal::LIGHT_FILE_NAME),
+    ];
+    if with_settings{
+        inputs.push(process_dir.join(config::PROCESS_SETTING_FILE_NAME));
+    }
+    inputs.extend((0..config::TAKE_COUNT).map(|i|process_dir.join(config::take_file_name(i))));
+    if let Some(root) = process_dir.parent(){
+        inputs.push(root.join(config::LENS_CARIBRATION_FILE_NAME));
+        inputs.push(root.join(normal::LIGHT_FILE_NAME));
+    }
+    inputs.iter().filter_map(|path|modified(path)).max()
+}
+
+fn modified(path: &Path)->Option<SystemTime>{
+    std::fs::metadata(path).and_then(|metadata|metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::super::exposure::Exposure;
+    use super::super::super::testing::{self, TempDir};
+
+    fn maps(scan: &ProcessScan)->[MapState; 3]{
+        [scan.normal, scan.albedo, scan.roughness]
+    }
+
+    fn process(process_dir: &Path){
+        let mut settings = testing::load_settings(process_dir);
+        pipeline::run(process_dir, &mut settings, &mut |_, _|Ok(())).unwrap();
+    }
+
+    #[test]
+    fn maps_are_missing_until_processed(){
+        let dir = TempDir::new("status_missing");
+        let process_dir = dir.path().join("sample");
+        testing::write_process(&process_dir, serde_json::json!({}));
+        let scan = scan(&process_dir);
+        assert_eq!(scan.name, "sample");
+        assert!(scan.has_gray && scan.has_takes.iter().all(|taken|*taken));
+        assert_eq!(maps(&scan), [MapState::Missing; 3]);
+        assert_eq!(scan.status, ProcessStatus::Captured);
+        process(&process_dir);
+        let scan = super::scan(&process_dir);
+        assert_eq!(maps(&scan), [MapState::Current; 3]);
+        assert_eq!(scan.status, ProcessStatus::Processed);
+    }
+
+    #[test]
+    fn an_exposure_change_keeps_the_maps_current(){
+        let dir = TempDir::new("status_exposure");
+        let process_dir = dir.path().join("sample");
+        testing::write_process(&process_dir, serde_json::json!({}));
+        process(&process_dir);
+        // as the exposure controls write it
+        let path = process_dir.join(config::PROCESS_SETTING_FILE_NAME);
+        let mut settings = testing::load_settings(&process_dir);
+        settings.set_exposure(config::ExposureSettings::from_exposure(&Exposure::parse("400", "8.0", "1/60").unwrap()));
+        settings.save(&path).unwrap();
+        testing::touch_later(&path);
+        assert_eq!(maps(&scan(&process_dir)), [MapState::Current; 3]);
+    }
+
+    #[test]
+    fn other_options_or_frames_make_the_maps_stale(){
+        let dir = TempDir::new("status_stale");
+        let process_dir = dir.path().join("sample");
+        testing::write_process(&process_dir, serde_json::json!({}));
+        process(&process_dir);
+        testing::set_options(&process_dir, serde_json::json!({"tiling_blend": 0.25}));
+        assert_eq!(maps(&scan(&process_dir)), [MapState::Stale; 3]);
+        assert_eq!(scan(&process_dir).status, ProcessStatus::Captured);
+        process(&process_dir);
+        assert_eq!(maps(&scan(&process_dir)), [MapState::Current; 3]);
+        testing::touch_later(&process_dir.join(config::take_file_name(3)));
+        assert_eq!(maps(&scan(&process_dir)), [MapState::Stale; 3]);
+    }
+
+    #[test]
+    fn without_the_cache_the_time_of_the_settings_counts(){
+        let dir = TempDir::new("status_no_cache");
+        let process_dir = dir.path().join("sample");
+        testing::write_process(&process_dir, serde_json::json!({}));
+        process(&process_dir);
+        std::fs::remove_dir_all(process_dir.join(config::PROCESS_CACHE_DIR_NAME)).unwrap();
+        assert_eq!(maps(&scan(&process_dir)), [MapState::Current; 3]);
+        testing::touch_later(&process_dir.join(config::PROCESS_SETTING_FILE_NAME));
+        assert_eq!(maps(&scan(&process_dir)), [MapState::Stale; 3]);
+    }
+}
@@ -0,0 +1,98 @@
+// makes the maps tileable by cross blending opposite edges
+use super::{linalg, Image};
+use super::super::error::{Error, Result};
+
+// the last blend * width columns (and blend * height rows) are faded into the
+// first ones, so the result is smaller by that much and wraps around without a seam
+pub fn tile(img: &Image, blend: f32)->Result<Image>{
+    if !(blend > 0.0 && blend <= 0.5){
+        return Err(Error::validation("tile the maps", &format!("tiling blend {} is out of range (0, 0.5]", blend)));
+    }
+    let horizontal = blend_edges(img, ((img.width() as f32 * blend).round() as u32).max(1), true);
+    Ok(blend_edges(&horizontal, ((img.height() as f32 * blend).round() as u32).max(1), false))
+}
+
+// tile unit normals and renormalise the blended vectors
+pub fn tile_normals(normals: &Image, blend: f32)->Result<Image>{
+    let mut tiled = tile(normals, blend)?;
+    tiled.map(|n|{
+        linalg::normalize3([n[0] as f64, n[1] as f64, n[2] as f64])
+            .map(|v|[v[0] as f32, v[1] as f32, v[2] as f32])
+            .unwrap_or([0.0, 0.0, 1.0])
+    });
+    Ok(tiled)
+}
+
+fn blend_edges(img: &Image, band: u32, horizontal: bool)->Image{
+    let (width, height) = if horizontal{
+        (img.width() - band, img.height())
+    }
+    else{
+        (img.width(), img.height() - band)
+    };
+    let length = if horizontal { width } else { height };
+    let mut tiled = Image::new(width, height);
+    for y in 0..height{
+        for x in 0..width{
+            let pos = if horizontal { x } else { y };
+            let p = img.get(x, y);
+            let value = if pos < band{
+                // fade in from the pixel that follows the opposite edge
+                let (wx, wy) = if horizontal { (x + length, y) } else { (x, y + length) };
+                let q = img.get(wx, wy);
+                let t = (pos as f32 + 0.5) / band as f32;
+                [q[0] + (p[0] - q[0]) * t, q[1] + (p[1] - q[1]) * t, q[2] + (p[2] - q[2]) * t]
+            }
+            else{
+                p
+            };
+            tiled.set(x, y, value);
+        }
+    }
+    tiled
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn tile_fades_the_far_edge_into_the_near_one(){
+        let mut img = Image::new(10, 10);
+        for y in 0..10{
+            for x in 0..10{
+                img.set(x, y, [x as f32, y as f32, 0.0]);
+            }
+        }
+        // a band of 2 pixels: pixel 0 is 3/4 of pixel 8 and 1/4 of pixel 0, pixel 1 is 1/4 of pixel 9 and 3/4 of pixel 1
+        let tiled = tile(&img, 0.2).unwrap();
+        assert_eq!((tiled.width(), tiled.height()), (8, 8));
+        let row: Vec<f32> = (0..8).map(|x|tiled.get(x, 5)[0]).collect();
+        assert_eq!(row, vec![6.0, 3.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]);
+        let column: Vec<f32> = (0..8).map(|y|tiled.get(5, y)[1]).collect();
+        assert_eq!(column, vec![6.0, 3.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0]);
+    }
+
+    #[test]
+    fn tile_rejects_a_blend_out_of_range(){
+        let img = Image::new(4, 4);
+        assert!(matches!(tile(&img, 0.0), Err(Error::Validation{..})));
+        assert!(matches!(tile(&img, 0.6), Err(Error::Validation{..})));
+        assert!(matches!(tile(&img, f32::NAN), Err(Error::Validation{..})));
+    }
+
+    #[test]
+    fn tile_normals_keeps_unit_length(){
+        let mut normals = Image::new(8, 8);
+        for y in 0..8{
+            for x in 0..8{
+                let tilt = x as f32 / 8.0;
+                normals.set(x, y, [tilt, 0.0, (1.0 - tilt * tilt).sqrt()]);
+            }
+        }
+        let tiled = tile_normals(&normals, 0.25).unwrap();
+        for n in tiled.pixels(){
+            assert!(((n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt() - 1.0).abs() < 1e-5, "{:?}", n);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+// perspective rectification of the sample quad to a square texture
+use super::{linalg, Image};
+use super::super::error::{Error, Result};
+
+// corners are in 0..1 frame coordinates (y down) ordered
+// bottom-left, top-left, top-right, bottom-right like ProcessingOptions::trim_point
+pub fn rectify(img: &Image, corners: &[(f32, f32); 4], resolution: u32)->Result<Image>{
+    if resolution == 0{
+        return Err(Error::validation("trim the frames", "trim resolution must not be zero"));
+    }
+    let size = resolution as f64;
+    let (width, height) = (img.width() as f64, img.height() as f64);
+    let dst = [(0.0, size), (0.0, 0.0), (size, 0.0), (size, size)];
+    let mut src = [(0.0, 0.0); 4];
+    for i in 0..4{
+        src[i] = (corners[i].0 as f64 * width, corners[i].1 as f64 * height);
+    }
+    // map output pixels back into the frame
+    let h = linalg::homography(&dst, &src).ok_or_else(||Error::validation("trim the frames", "trim points do not form a quad"))?;
+    let mut trimmed = Image::new(resolution, resolution);
+    for y in 0..resolution{
+        for x in 0..resolution{
+            let (u, v) = linalg::project(&h, x as f64 + 0.5, y as f64 + 0.5);
+            trimmed.set(x, y, img.sample(u as f32, v as f32));
+        }
+    }
+    Ok(trimmed)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    // red is the column and green the row of the pixel, bilinear sampling reproduces it exactly
+    fn gradient(width: u32, height: u32)->Image{
+        let mut img = Image::new(width, height);
+        for y in 0..height{
+            for x in 0..width{
+                img.set(x, y, [x as f32, y as f32, 0.0]);
+            }
+        }
+        img
+    }
+
+    #[test]
+    fn rectify_follows_a_known_homography(){
+        let (width, height, resolution) = (64u32, 48u32, 16u32);
+        // output pixels to frame pixels
+        let h = [[2.0, 0.3, 10.0], [0.2, 1.8, 8.0], [0.002, 0.001, 1.0]];
+        let size = resolution as f64;
+        let mut corners = [(0.0, 0.0); 4];
+        for (corner, (x, y)) in corners.iter_mut().zip([(0.0, size), (0.0, 0.0), (size, 0.0), (size, size)].iter()){
+            let (u, v) = linalg::project(&h, *x, *y);
+            *corner = ((u / width as f64) as f32, (v / height as f64) as f32);
+        }
+        let trimmed = rectify(&gradient(width, height), &corners, resolution).unwrap();
+        assert_eq!((trimmed.width(), trimmed.height()), (resolution, resolution));
+        for y in 0..resolution{
+            for x in 0..resolution{
+                let (u, v) = linalg::project(&h, x as f64 + 0.5, y as f64 + 0.5);
+                let p = trimmed.get(x, y);
+                assert!((p[0] as f64 - (u - 0.5)).abs() < 1e-3 && (p[1] as f64 - (v - 0.5)).abs() < 1e-3, "{:?} at {} {}", p, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn rectify_of_the_full_frame_keeps_the_image(){
+        let img = gradient(8, 8);
+        let trimmed = rectify(&img, &[(0.0, 1.0), (0.0, 0.0), (1.0, 0.0), (1.0, 1.0)], 8).unwrap();
+        for (a, b) in trimmed.pixels().iter().zip(img.pixels()){
+            assert!((a[0] - b[0]).abs() < 1e-4 && (a[1] - b[1]).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn rectify_rejects_bad_input(){
+        let img = gradient(8, 8);
+        assert!(matches!(rectify(&img, &[(0.0, 1.0), (0.0, 0.0), (1.0, 0.0), (1.0, 1.0)], 0), Err(Error::Validation{..})));
+        assert!(matches!(rectify(&img, &[(0.5, 0.5); 4], 8), Err(Error::Validation{..})));
+    }
+}
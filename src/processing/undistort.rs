@@ -0,0 +1,181 @@
+// lens undistortion with the opencv style camera model of LensCaribrationInfo
+use super::{config, Image};
+use super::super::error::{Error, Result};
+
+// camera matrix (fx, fy, cx, cy) and (k1, k2, p1, p2, k3) at one focal length
+pub struct LensModel{
+    fx: f64,
+    fy: f64,
+    cx: f64,
+    cy: f64,
+    dist: [f64; 5],
+    // size of the calibration images, None for calibrations stored before it was recorded
+    size: Option<(u32, u32)>,
+}
+
+impl LensModel{
+    fn from_calibration(mat: &[f32], dist: &[f32])->Option<Self>{
+        if mat.len() != 9 || dist.len() < 4 || mat[0] <= 0.0 || mat[4] <= 0.0{
+            return None;
+        }
+        let mut d = [0.0; 5];
+        for (i, v) in dist.iter().take(5).enumerate(){
+            d[i] = *v as f64;
+        }
+        Some(LensModel{fx: mat[0] as f64, fy: mat[4] as f64, cx: mat[2] as f64, cy: mat[5] as f64, dist: d, size: None})
+    }
+    fn lerp(a: &LensModel, b: &LensModel, t: f64)->LensModel{
+        let mix = |x: f64, y: f64|x + (y - x) * t;
+        let mut dist = [0.0; 5];
+        for (i, d) in dist.iter_mut().enumerate(){
+            *d = mix(a.dist[i], b.dist[i]);
+        }
+        LensModel{fx: mix(a.fx, b.fx), fy: mix(a.fy, b.fy), cx: mix(a.cx, b.cx), cy: mix(a.cy, b.cy), dist, size: a.size}
+    }
+    // model at focal_length, linearly interpolated between the wide and tele calibrations.
+    // None when nothing usable is calibrated.
+    pub fn at_focal_length(info: &config::LensCaribrationInfo, focal_length: f32)->Option<Self>{
+        let (mat, dist) = info.get_wide();
+        let wide = Self::from_calibration(mat, dist);
+        let (mat, dist) = info.get_tele();
+        let tele = Self::from_calibration(mat, dist);
+        let model = match (wide, tele){
+            (Some(wide), Some(tele))=>{
+                let (fw, ft) = (info.get_focal_length_wide() as f64, info.get_focal_length_tele() as f64);
+                let t = if (ft - fw).abs() > 1e-3 { ((focal_length as f64 - fw) / (ft - fw)).clamp(0.0, 1.0) } else { 0.0 };
+                Some(Self::lerp(&wide, &tele, t))
+            }
+            (Some(model), None) | (None, Some(model))=>Some(model),
+            (None, None)=>None,
+        };
+        model.map(|model|LensModel{size: info.get_image_size(), ..model})
+    }
+    // fx, fy, cx, cy and the distortion coefficients, e.g. for a cache key
+    pub fn parameters(&self)->[f64; 9]{
+        let [k1, k2, p1, p2, k3] = self.dist;
+        [self.fx, self.fy, self.cx, self.cy, k1, k2, p1, p2, k3]
+    }
+    // distorted pixel position of an ideal one, pixel centers are on integers
+    pub fn distort(&self, u: f64, v: f64)->(f64, f64){
+        let x = (u - self.cx) / self.fx;
+        let y = (v - self.cy) / self.fy;
+        let [k1, k2, p1, p2, k3] = self.dist;
+        let r2 = x * x + y * y;
+        let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+        let xd = x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x);
+        let yd = y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y;
+        (xd * self.fx + self.cx, yd * self.fy + self.cy)
+    }
+}
+
+// remap a frame so that straight lines in the scene are straight in the image
+pub fn undistort(img: &Image, model: &LensModel)->Result<Image>{
+    // the camera matrix is in pixels of the calibration images, it does not fit frames of another size
+    if let Some((width, height)) = model.size{
+        if (img.width(), img.height()) != (width, height){
+            let message = format!("the lens was calibrated with {}x{} images, the frames are {}x{}. calibrate again at the capture size", width, height, img.width(), img.height());
+            return Err(Error::processing("undistort the frames", &message));
+        }
+    }
+    let mut undistorted = Image::new(img.width(), img.height());
+    for y in 0..img.height(){
+        for x in 0..img.width(){
+            let (u, v) = model.distort(x as f64, y as f64);
+            undistorted.set(x, y, img.sample(u as f32 + 0.5, v as f32 + 0.5));
+        }
+    }
+    Ok(undistorted)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    // (fx, fy, cx, cy) and k1 as a calibration stores them
+    fn calibration(fx: f32, fy: f32, cx: f32, cy: f32, k1: f32)->(Vec<f32>, Vec<f32>){
+        (vec![fx, 0.0, cx, 0.0, fy, cy, 0.0, 0.0, 1.0], vec![k1, 0.01, 0.001, -0.002, 0.0])
+    }
+
+    fn zoom_lens()->config::LensCaribrationInfo{
+        let mut info = config::LensCaribrationInfo::new();
+        let (mat, dist) = calibration(800.0, 790.0, 320.0, 240.0, -0.2);
+        info.set_wide(24.0, mat, dist);
+        let (mat, dist) = calibration(2400.0, 2380.0, 330.0, 236.0, 0.1);
+        info.set_tele(70.0, mat, dist);
+        info
+    }
+
+    fn assert_parameters(model: &LensModel, expected: [f64; 9]){
+        for (i, (a, b)) in model.parameters().iter().zip(expected.iter()).enumerate(){
+            assert!((a - b).abs() < 1e-6, "parameter {}: {} != {}", i, a, b);
+        }
+    }
+
+    // red is the column and green the row of the pixel, bilinear sampling reproduces it exactly
+    fn gradient(width: u32, height: u32)->Image{
+        let mut img = Image::new(width, height);
+        for y in 0..height{
+            for x in 0..width{
+                img.set(x, y, [x as f32, y as f32, 0.0]);
+            }
+        }
+        img
+    }
+
+    #[test]
+    fn the_ends_of_the_zoom_use_their_own_calibration(){
+        let info = zoom_lens();
+        let wide = [800.0, 790.0, 320.0, 240.0, -0.2, 0.01, 0.001, -0.002, 0.0];
+        let tele = [2400.0, 2380.0, 330.0, 236.0, 0.1, 0.01, 0.001, -0.002, 0.0];
+        assert_parameters(&LensModel::at_focal_length(&info, 24.0).unwrap(), wide);
+        assert_parameters(&LensModel::at_focal_length(&info, 70.0).unwrap(), tele);
+        // outside the calibrated range the nearest end is used, also for an unknown focal length
+        assert_parameters(&LensModel::at_focal_length(&info, 200.0).unwrap(), tele);
+        assert_parameters(&LensModel::at_focal_length(&info, 0.0).unwrap(), wide);
+    }
+
+    #[test]
+    fn a_focal_length_between_the_ends_is_interpolated(){
+        let info = zoom_lens();
+        // a quarter of the way from 24mm to 70mm
+        let model = LensModel::at_focal_length(&info, 35.5).unwrap();
+        assert_parameters(&model, [1200.0, 1187.5, 322.5, 239.0, -0.125, 0.01, 0.001, -0.002, 0.0]);
+    }
+
+    #[test]
+    fn one_calibrated_end_is_used_for_every_focal_length(){
+        let mut info = config::LensCaribrationInfo::new();
+        assert!(LensModel::at_focal_length(&info, 50.0).is_none());
+        let (mat, dist) = calibration(800.0, 790.0, 320.0, 240.0, -0.2);
+        info.set_wide(24.0, mat, dist);
+        assert_parameters(&LensModel::at_focal_length(&info, 50.0).unwrap(), [800.0, 790.0, 320.0, 240.0, -0.2, 0.01, 0.001, -0.002, 0.0]);
+    }
+
+    #[test]
+    fn undistort_samples_the_distorted_position(){
+        let mut info = config::LensCaribrationInfo::new();
+        let (mat, dist) = calibration(60.0, 60.0, 32.0, 24.0, -0.1);
+        info.set_wide(24.0, mat, dist);
+        let model = LensModel::at_focal_length(&info, 24.0).unwrap();
+        let undistorted = undistort(&gradient(64, 48), &model).unwrap();
+        for &(x, y) in [(32, 24), (10, 8), (50, 40), (5, 30)].iter(){
+            let (u, v) = model.distort(x as f64, y as f64);
+            let pixel = undistorted.get(x, y);
+            assert!((pixel[0] as f64 - u).abs() < 1e-3 && (pixel[1] as f64 - v).abs() < 1e-3, "({}, {}): {:?} != ({}, {})", x, y, pixel, u, v);
+        }
+        // the principal point does not move, a corner is pulled in by the barrel distortion
+        assert_eq!(undistorted.get(32, 24), [32.0, 24.0, 0.0]);
+        assert!(model.distort(5.0, 30.0).0 > 5.0);
+    }
+
+    #[test]
+    fn undistort_refuses_frames_of_another_size(){
+        let mut info = config::LensCaribrationInfo::new();
+        let (mat, dist) = calibration(60.0, 60.0, 32.0, 24.0, -0.1);
+        info.set_wide(24.0, mat, dist);
+        info.set_image_size(64, 48);
+        let model = LensModel::at_focal_length(&info, 24.0).unwrap();
+        assert!(undistort(&gradient(64, 48), &model).is_ok());
+        assert!(matches!(undistort(&gradient(48, 64), &model), Err(Error::Processing{..})));
+    }
+}
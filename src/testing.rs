@@ -0,0 +1,71 @@
+// helpers for the unit tests
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use super::config;
+use super::processing::{normal, Image};
+
+static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+// an empty directory under the temp dir, removed again when dropped
+pub struct TempDir{
+    path: PathBuf,
+}
+
+impl TempDir{
+    pub fn new(name: &str)->Self{
+        let path = std::env::temp_dir().join(format!("tex_shooter_{}_{}_{}", name, std::process::id(), NEXT_DIR.fetch_add(1, Ordering::SeqCst)));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir{path}
+    }
+    pub fn path(&self)->&Path{
+        &self.path
+    }
+}
+
+impl Drop for TempDir{
+    fn drop(&mut self){
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+// a process of a flat sample shot under the default lights, small enough to run the pipeline in a test.
+// the corners are the full frame, options are set on top of that in process.json
+pub fn write_process(process_dir: &Path, options: serde_json::Value){
+    std::fs::create_dir_all(process_dir).unwrap();
+    config::ProcessingSettings::new().save(&process_dir.join(config::PROCESS_SETTING_FILE_NAME)).unwrap();
+    set_options(process_dir, serde_json::json!({"auto_trimming": false, "trim_resolution": 8}));
+    set_options(process_dir, options);
+    let frame = |value: f32|{
+        let mut img = Image::new(16, 16);
+        img.map(|_|[value; 3]);
+        img
+    };
+    frame(0.5).save(&process_dir.join(config::GRAY_FILE_NAME)).unwrap();
+    for (i, light) in normal::default_light_directions().iter().enumerate(){
+        frame(0.6 * light[2]).save(&process_dir.join(config::take_file_name(i))).unwrap();
+    }
+}
+
+// change processing options in process.json of process_dir, the others stay as they are
+pub fn set_options(process_dir: &Path, options: serde_json::Value){
+    let path = process_dir.join(config::PROCESS_SETTING_FILE_NAME);
+    let mut settings: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    for (name, value) in options.as_object().unwrap(){
+        settings["options"][name] = value.clone();
+    }
+    std::fs::write(&path, settings.to_string()).unwrap();
+}
+
+// process.json of process_dir
+pub fn load_settings(process_dir: &Path)->config::ProcessingSettings{
+    let mut settings = config::ProcessingSettings::new();
+    settings.load(&process_dir.join(config::PROCESS_SETTING_FILE_NAME)).unwrap();
+    settings
+}
+
+// move the modification time of path ahead, file times are too coarse on some file systems to tell writes apart
+pub fn touch_later(path: &Path){
+    let file = std::fs::OpenOptions::new().write(true).open(path).unwrap();
+    file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(60)).unwrap();
+}